@@ -1,29 +1,329 @@
-pub type Mass = i32;
-pub type Fuel = i32;
+use std::fmt;
+use std::iter::FusedIterator;
 
+use crate::input::error_at;
+
+// Masses and fuel are kept as u128 so any u64 module mass, and the totals of
+// many of them, fit without overflow.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub struct Mass(pub u128);
+
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub struct Fuel(pub u128);
+
+impl Mass {
+    pub fn checked_add(self, other: Mass) -> Option<Mass> {
+        self.0.checked_add(other.0).map(Mass)
+    }
+
+    // The fuel needed to launch this mass alone, never negative.
+    pub fn fuel(self) -> Fuel {
+        Fuel((self.0 / 3).saturating_sub(2))
+    }
+
+    // Each extra amount of fuel needed: first for the mass itself, then for
+    // the fuel just added, until no more is needed.
+    pub fn increments(self) -> Increments {
+        Increments { mass: self }
+    }
+}
+
+impl Fuel {
+    pub fn checked_add(self, other: Fuel) -> Option<Fuel> {
+        self.0.checked_add(other.0).map(Fuel)
+    }
+
+    pub fn checked_sub(self, other: Fuel) -> Option<Fuel> {
+        self.0.checked_sub(other.0).map(Fuel)
+    }
+
+    // The sum of `fuel`, or None if it doesn't fit.
+    pub fn total<I: IntoIterator<Item = Fuel>>(fuel: I) -> Option<Fuel> {
+        fuel.into_iter()
+            .try_fold(Fuel(0), |total, f| total.checked_add(f))
+    }
+}
+
+impl From<u64> for Mass {
+    fn from(mass: u64) -> Mass {
+        Mass(mass as u128)
+    }
+}
+
+impl From<u128> for Mass {
+    fn from(mass: u128) -> Mass {
+        Mass(mass)
+    }
+}
+
+// Fuel has mass too.
+impl From<Fuel> for Mass {
+    fn from(fuel: Fuel) -> Mass {
+        Mass(fuel.0)
+    }
+}
+
+impl fmt::Display for Mass {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        self.0.fmt(f)
+    }
+}
+
+impl fmt::Display for Fuel {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        self.0.fmt(f)
+    }
+}
+
+pub struct Increments {
+    mass: Mass,
+}
+
+impl Iterator for Increments {
+    type Item = Fuel;
+
+    fn next(&mut self) -> Option<Fuel> {
+        let fuel = self.mass.fuel();
+        if fuel == Fuel(0) {
+            return None;
+        }
+        self.mass = fuel.into();
+        Some(fuel)
+    }
+}
+
+impl FusedIterator for Increments {}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Mode {
+    // fuel for the module's mass only, from the formula
+    ClosedForm,
+    // also fuel for the fuel, repeatedly
+    Iterative,
+}
+
+impl Mode {
+    pub fn fuel(self, mass: Mass) -> Fuel {
+        match self {
+            Mode::ClosedForm => mass.fuel(),
+            Mode::Iterative => ModuleFuel::new(mass).total(),
+        }
+    }
+}
+
+// Each increment is at most a third of the one before, so all of them add up
+// to less than half the mass and the plain sums below can't overflow.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct ModuleFuel {
+    pub mass: Mass,
+    // fuel for the module's own mass
+    pub direct: Fuel,
+    // fuel for the fuel
+    pub extra: Fuel,
+    // the number of increments needed in all
+    pub steps: usize,
+}
+
+impl ModuleFuel {
+    pub fn new(mass: Mass) -> ModuleFuel {
+        let direct = mass.fuel();
+        let mut extra = Fuel(0);
+        let mut steps = 0;
+
+        for fuel in mass.increments() {
+            steps += 1;
+            if steps > 1 {
+                extra = Fuel(extra.0 + fuel.0);
+            }
+        }
+
+        ModuleFuel {
+            mass,
+            direct,
+            extra,
+            steps,
+        }
+    }
+
+    pub fn total(&self) -> Fuel {
+        Fuel(self.direct.0 + self.extra.0)
+    }
+}
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Report {
+    pub modules: Vec<ModuleFuel>,
+    pub direct: Fuel,
+    pub extra: Fuel,
+}
+
+impl Report {
+    pub fn new(masses: &[Mass]) -> Result<Report, String> {
+        let modules: Vec<ModuleFuel> = masses.iter().map(|&m| ModuleFuel::new(m)).collect();
+        let overflow = || String::from("total fuel doesn't fit in 128 bits");
+
+        Ok(Report {
+            direct: Fuel::total(modules.iter().map(|m| m.direct)).ok_or_else(overflow)?,
+            extra: Fuel::total(modules.iter().map(|m| m.extra)).ok_or_else(overflow)?,
+            modules,
+        })
+    }
+
+    pub fn total(&self, mode: Mode) -> Option<Fuel> {
+        match mode {
+            Mode::ClosedForm => Some(self.direct),
+            Mode::Iterative => self.direct.checked_add(self.extra),
+        }
+    }
+}
+
+impl fmt::Display for Report {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        writeln!(
+            f,
+            "{:>12} {:>12} {:>12} {:>12}",
+            "mass", "fuel", "extra", "total"
+        )?;
+        for m in &self.modules {
+            writeln!(
+                f,
+                "{:>12} {:>12} {:>12} {:>12}",
+                m.mass,
+                m.direct,
+                m.extra,
+                m.total()
+            )?;
+        }
+
+        let total = match self.total(Mode::Iterative) {
+            Some(fuel) => fuel.to_string(),
+            None => String::from("overflow"),
+        };
+        writeln!(
+            f,
+            "{:>12} {:>12} {:>12} {:>12}",
+            "", self.direct, self.extra, total
+        )
+    }
+}
+
+// One mass per line, skipping blank lines.
 #[aoc_generator(day1)]
-pub fn input_generator(input: &str) -> Vec<Mass> {
+pub fn input_generator(input: &str) -> Result<Vec<Mass>, String> {
     input
         .lines()
-        .map(|line| line.trim().parse().unwrap())
+        .enumerate()
+        .filter(|(_, line)| !line.trim().is_empty())
+        .map(|(row, line)| {
+            let indent = line.len() - line.trim_start().len();
+            let mass = line.trim();
+            mass.parse()
+                .map(Mass)
+                .map_err(|_| error_at(row, line, indent, format!("invalid mass \"{}\"", mass)))
+        })
         .collect()
 }
 
-#[aoc(day1, part1)]
-pub fn solve_part1(input: &[Mass]) -> Fuel {
-    input.iter().map(|m| (m / 3) - 2).sum()
+fn total_fuel(input: &[Mass], mode: Mode) -> Result<Fuel, String> {
+    Fuel::total(input.iter().map(|&m| mode.fuel(m)))
+        .ok_or_else(|| String::from("total fuel doesn't fit in 128 bits"))
 }
 
-fn total_module_fuel(m: Mass) -> Fuel {
-    let f: Fuel = (m / 3) - 2;
-    if f < 0 {
-        0
-    } else {
-        f + total_module_fuel(f)
-    }
+#[aoc(day1, part1)]
+pub fn solve_part1(input: &[Mass]) -> Result<Fuel, String> {
+    total_fuel(input, Mode::ClosedForm)
 }
 
 #[aoc(day1, part2)]
-pub fn solve_part2(input: &[Mass]) -> Fuel {
-    input.iter().map(|&m| total_module_fuel(m)).sum()
+pub fn solve_part2(input: &[Mass]) -> Result<Fuel, String> {
+    total_fuel(input, Mode::Iterative)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn examples() {
+        for &(mass, fuel) in &[(12, 2), (14, 2), (1969, 654), (100756, 33583)] {
+            assert_eq!(Mass(mass).fuel(), Fuel(fuel));
+        }
+        for &(mass, fuel) in &[(14, 2), (1969, 966), (100756, 50346)] {
+            assert_eq!(Mode::Iterative.fuel(Mass(mass)), Fuel(fuel));
+        }
+        assert_eq!(Mass(5).fuel(), Fuel(0));
+    }
+
+    #[test]
+    fn increments() {
+        let steps: Vec<Fuel> = Mass(1969).increments().collect();
+        assert_eq!(
+            steps,
+            [654, 216, 70, 21, 5]
+                .iter()
+                .map(|&f| Fuel(f))
+                .collect::<Vec<_>>()
+        );
+        assert_eq!(Mass(8).increments().next(), None);
+    }
+
+    #[test]
+    fn huge_masses() {
+        let mass = Mass::from(u64::MAX);
+        assert_eq!(mass.fuel(), Fuel(u64::MAX as u128 / 3 - 2));
+        assert!(Mode::Iterative.fuel(mass) < Fuel(u64::MAX as u128 / 2));
+
+        let mass = Mass(u128::MAX);
+        assert_eq!(
+            Some(Mode::Iterative.fuel(mass)),
+            Fuel::total(mass.increments())
+        );
+        assert_eq!(
+            solve_part1(&[mass, mass, mass]).map(|f| f.0),
+            Ok(u128::MAX - 6)
+        );
+        assert!(solve_part1(&[mass; 4]).is_err());
+        assert!(Report::new(&[mass; 4]).is_err());
+        assert_eq!(Fuel(1).checked_sub(Fuel(2)), None);
+    }
+
+    #[test]
+    fn report() {
+        let report = Report::new(&[Mass(14), Mass(1969)]).unwrap();
+        assert_eq!(
+            report.modules[1],
+            ModuleFuel {
+                mass: Mass(1969),
+                direct: Fuel(654),
+                extra: Fuel(312),
+                steps: 5,
+            }
+        );
+        assert_eq!(report.total(Mode::ClosedForm), Some(Fuel(656)));
+        assert_eq!(report.total(Mode::Iterative), Some(Fuel(968)));
+
+        let table = report.to_string();
+        assert_eq!(table.lines().count(), 4);
+        assert!(table
+            .lines()
+            .last()
+            .unwrap()
+            .ends_with("656          312          968"));
+    }
+
+    #[test]
+    fn malformed_input() {
+        assert_eq!(
+            input_generator("12\n14 \n\n1969\n\n"),
+            Ok(vec![Mass(12), Mass(14), Mass(1969)])
+        );
+        assert_eq!(
+            input_generator("12\n  -14\n"),
+            Err(String::from("line 2, column 3: invalid mass \"-14\""))
+        );
+        assert_eq!(
+            input_generator("12\n1 4"),
+            Err(String::from("line 2, column 1: invalid mass \"1 4\""))
+        );
+    }
 }
@@ -1,33 +1,27 @@
+use crate::intcode;
+
 #[aoc_generator(day2)]
-pub fn input_generator(input: &str) -> Vec<u32> {
-    input.split(',').map(|i| i.parse().unwrap()).collect()
+pub fn input_generator(input: &str) -> Result<Vec<u32>, String> {
+    intcode::parse_values(input)
 }
 
-pub fn computer(input_s: &[u32], a: u32, b: u32) -> u32 {
-    let mut input = Vec::new();
-    let mut iter = input_s.iter();
-
-    input.resize_with(input_s.len(), || *iter.next().unwrap());
-
-    input[1] = a;
-    input[2] = b;
-
+pub fn run(memory: &mut [u32]) {
     let mut pos = 0usize;
 
     loop {
-        let op = input[pos];
+        let op = memory[pos];
         match op {
             1 => {
-                let a = input[input[pos + 1] as usize];
-                let b = input[input[pos + 2] as usize];
-                let c = input[pos + 3];
-                input[c as usize] = a + b;
+                let a = memory[memory[pos + 1] as usize];
+                let b = memory[memory[pos + 2] as usize];
+                let c = memory[pos + 3];
+                memory[c as usize] = a + b;
             }
             2 => {
-                let a = input[input[pos + 1] as usize];
-                let b = input[input[pos + 2] as usize];
-                let c = input[pos + 3];
-                input[c as usize] = a * b;
+                let a = memory[memory[pos + 1] as usize];
+                let b = memory[memory[pos + 2] as usize];
+                let c = memory[pos + 3];
+                memory[c as usize] = a * b;
             }
             99 => break,
             _ => panic!("Unknown opcode"),
@@ -35,6 +29,14 @@ pub fn computer(input_s: &[u32], a: u32, b: u32) -> u32 {
 
         pos += 4;
     }
+}
+
+pub fn computer(input_s: &[u32], a: u32, b: u32) -> u32 {
+    let mut input = input_s.to_vec();
+
+    input[1] = a;
+    input[2] = b;
+    run(&mut input);
 
     input[0]
 }
@@ -58,3 +60,17 @@ pub fn solve_part2(input: &[u32]) -> Option<u32> {
 
     None
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn malformed_input() {
+        assert_eq!(input_generator("1,0,0,0,99\n"), Ok(vec![1, 0, 0, 0, 99]));
+        assert_eq!(
+            input_generator("1,0,-1,0,99"),
+            Err(String::from("line 1, column 5: invalid value \"-1\""))
+        );
+    }
+}
@@ -1,132 +1,1161 @@
+use std::collections::BTreeMap;
+use std::collections::HashMap;
+use std::collections::HashSet;
+use std::mem;
 use std::str::FromStr;
 
-#[derive(Clone, Copy, PartialEq, Eq)]
+use crate::input::error_at;
+
+pub mod render;
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
 pub enum Path {
     Up(u32),
     Down(u32),
     Left(u32),
     Right(u32),
+    UpLeft(u32),
+    UpRight(u32),
+    DownLeft(u32),
+    DownRight(u32),
+}
+
+impl Path {
+    // unit step and number of steps
+    fn direction(self) -> ((i64, i64), u32) {
+        match self {
+            Path::Up(n) => ((0, 1), n),
+            Path::Down(n) => ((0, -1), n),
+            Path::Left(n) => ((-1, 0), n),
+            Path::Right(n) => ((1, 0), n),
+            Path::UpLeft(n) => ((-1, 1), n),
+            Path::UpRight(n) => ((1, 1), n),
+            Path::DownLeft(n) => ((-1, -1), n),
+            Path::DownRight(n) => ((1, -1), n),
+        }
+    }
+
+    pub fn is_diagonal(self) -> bool {
+        let ((dx, dy), _) = self.direction();
+        dx != 0 && dy != 0
+    }
 }
 
 impl FromStr for Path {
     type Err = String;
 
     fn from_str(s: &str) -> Result<Self, Self::Err> {
-        let (d, n) = s.split_at(1);
-        if let Ok(n) = n.parse() {
-            match d {
-                "U" => Ok(Path::Up(n)),
-                "D" => Ok(Path::Down(n)),
-                "L" => Ok(Path::Left(n)),
-                "R" => Ok(Path::Right(n)),
-                _ => Err(format!("Path parse error - direction \"{}\"", d)),
+        let s = s.trim();
+        let split = s
+            .find(|c: char| !c.is_ascii_alphabetic())
+            .unwrap_or(s.len());
+        let (d, n) = s.split_at(split);
+
+        if d.is_empty() {
+            return Err(format!("missing direction in \"{}\"", s));
+        }
+
+        let n = n.parse().map_err(|_| format!("invalid size \"{}\"", n))?;
+
+        match d.to_ascii_uppercase().as_str() {
+            "U" => Ok(Path::Up(n)),
+            "D" => Ok(Path::Down(n)),
+            "L" => Ok(Path::Left(n)),
+            "R" => Ok(Path::Right(n)),
+            "UL" => Ok(Path::UpLeft(n)),
+            "UR" => Ok(Path::UpRight(n)),
+            "DL" => Ok(Path::DownLeft(n)),
+            "DR" => Ok(Path::DownRight(n)),
+            _ => Err(format!("unknown direction \"{}\"", d)),
+        }
+    }
+}
+
+// Parses one wire per non-blank line. Errors carry the 1-based line and
+// column of the offending move. Diagonal moves (UL, UR, DL, DR) are an
+// extension to the puzzle and only accepted when `diagonals` is set.
+pub fn parse_panel(input: &str, diagonals: bool) -> Result<Panel, String> {
+    let mut wires = Vec::new();
+
+    for (row, line) in input.lines().enumerate() {
+        if line.trim().is_empty() {
+            continue;
+        }
+
+        let mut wire = Vec::new();
+        let mut offset = 0;
+
+        for token in line.split(',') {
+            let leading = token.len() - token.trim_start().len();
+            let at = |msg: String| error_at(row, line, offset + leading, msg);
+
+            if token.trim().is_empty() {
+                return Err(at(String::from("empty move")));
             }
-        } else {
-            Err(String::from("Path parse error - invalid size"))
+
+            let path: Path = token.parse().map_err(at)?;
+            if path.is_diagonal() && !diagonals {
+                return Err(at(format!("diagonal move \"{}\"", token.trim())));
+            }
+
+            wire.push(path);
+            offset += token.len() + 1;
         }
+
+        wires.push(wire);
+    }
+
+    if wires.len() < 2 {
+        return Err(String::from("need at least two wires"));
     }
+
+    Ok(Panel::new(&wires))
 }
 
 #[aoc_generator(day3)]
-pub fn input_generator(input: &str) -> (Vec<Path>, Vec<Path>) {
-    let mut g: Vec<Vec<Path>> = input
-        .lines()
-        .map(|l| {
-            l.trim()
-                .split(',')
-                .map(|p| Path::from_str(p).unwrap())
-                .collect()
-        })
-        .collect();
-    (g.remove(0), g.remove(0))
-}
-
-#[derive(Clone, Copy, PartialEq, Eq)]
+pub fn input_generator(input: &str) -> Result<Panel, String> {
+    parse_panel(input, false)
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, PartialOrd, Ord, Hash)]
 pub struct Point {
-    x: i32,
-    y: i32,
+    pub x: i64,
+    pub y: i64,
 }
 
-fn intersection(a: &Vec<Point>, b: &Vec<Point>) -> Vec<Point> {
-    a.clone().into_iter().filter(|x| b.contains(x)).collect()
+pub const CENTRAL_PORT: Point = Point { x: 0, y: 0 };
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Metric {
+    Manhattan,
+    Chebyshev,
+    Euclidean,
 }
 
-fn path_points(path: &Vec<Path>) -> Vec<Point> {
-    let mut curr = Point { x: 0, y: 0 };
-    let mut points = vec![Point { x: 0, y: 0 }];
+impl Metric {
+    pub fn distance(self, from: Point, to: Point) -> f64 {
+        match self {
+            Metric::Euclidean => (self.key(from, to) as f64).sqrt(),
+            _ => self.key(from, to) as f64,
+        }
+    }
+
+    // Orders points exactly like `distance`, without the square root.
+    fn key(self, from: Point, to: Point) -> i128 {
+        let dx = i128::from(to.x - from.x).abs();
+        let dy = i128::from(to.y - from.y).abs();
+
+        match self {
+            Metric::Manhattan => dx + dy,
+            Metric::Chebyshev => dx.max(dy),
+            Metric::Euclidean => dx * dx + dy * dy,
+        }
+    }
+}
+
+#[derive(Clone, Copy)]
+struct Segment {
+    start: Point,
+    end: Point,
+    // steps taken along the wire before reaching `start`
+    steps: i64,
+}
+
+impl Segment {
+    fn is_horizontal(&self) -> bool {
+        self.start.y == self.end.y
+    }
+
+    fn is_diagonal(&self) -> bool {
+        self.start.x != self.end.x && self.start.y != self.end.y
+    }
+
+    // unit step from `start` towards `end`
+    fn direction(&self) -> (i64, i64) {
+        (
+            (self.end.x - self.start.x).signum(),
+            (self.end.y - self.start.y).signum(),
+        )
+    }
+
+    fn len(&self) -> i64 {
+        (self.end.x - self.start.x)
+            .abs()
+            .max((self.end.y - self.start.y).abs())
+    }
+
+    fn x_range(&self) -> (i64, i64) {
+        (self.start.x.min(self.end.x), self.start.x.max(self.end.x))
+    }
+
+    fn y_range(&self) -> (i64, i64) {
+        (self.start.y.min(self.end.y), self.start.y.max(self.end.y))
+    }
+
+    // orientation and the coordinate that stays fixed along the segment
+    fn line(&self) -> (bool, i64) {
+        if self.is_horizontal() {
+            (true, self.start.y)
+        } else {
+            (false, self.start.x)
+        }
+    }
+
+    // range of the coordinate that varies along the segment
+    fn span(&self) -> (i64, i64) {
+        if self.is_horizontal() {
+            self.x_range()
+        } else {
+            self.y_range()
+        }
+    }
+
+    fn contains(&self, p: Point) -> bool {
+        let (x_lo, x_hi) = self.x_range();
+        let (y_lo, y_hi) = self.y_range();
+        let (dx, dy) = self.direction();
+        let on_line = !self.is_diagonal() || (p.x - self.start.x) * dy == (p.y - self.start.y) * dx;
+        on_line && x_lo <= p.x && p.x <= x_hi && y_lo <= p.y && p.y <= y_hi
+    }
+
+    fn point(&self, k: i64) -> Point {
+        let (dx, dy) = self.direction();
+        Point {
+            x: self.start.x + k * dx,
+            y: self.start.y + k * dy,
+        }
+    }
+
+    // A diagonal step counts as one step, like a straight one.
+    fn steps_to(&self, p: Point) -> i64 {
+        self.steps + (p.x - self.start.x).abs().max((p.y - self.start.y).abs())
+    }
+}
+
+// A line through lattice points, named by the coordinate that stays fixed
+// along it: y for rows, x for columns, y - x for rising diagonals and y + x
+// for falling ones. Points on it are numbered by x, or by y on a column.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+enum Line {
+    Row(i64),
+    Column(i64),
+    Rising(i64),
+    Falling(i64),
+}
+
+impl Line {
+    fn point(self, t: i64) -> Point {
+        match self {
+            Line::Row(y) => Point { x: t, y },
+            Line::Column(x) => Point { x, y: t },
+            Line::Rising(c) => Point { x: t, y: c + t },
+            Line::Falling(c) => Point { x: t, y: c - t },
+        }
+    }
+}
+
+impl Segment {
+    // The line the segment lies on and the range it covers along it.
+    fn line_span(&self) -> (Line, (i64, i64)) {
+        let (dx, dy) = self.direction();
+        let line = if dx != 0 && dy != 0 {
+            if dx == dy {
+                Line::Rising(self.start.y - self.start.x)
+            } else {
+                Line::Falling(self.start.y + self.start.x)
+            }
+        } else if self.is_horizontal() {
+            Line::Row(self.start.y)
+        } else {
+            Line::Column(self.start.x)
+        };
+
+        match line {
+            Line::Column(_) => (line, self.y_range()),
+            _ => (line, self.x_range()),
+        }
+    }
+}
+
+// The ranges a wire covers on each of its lines, sorted, with overlapping
+// and touching ranges merged.
+fn line_spans(wire: &[Segment]) -> HashMap<Line, Vec<(i64, i64)>> {
+    let mut lines: HashMap<Line, Vec<(i64, i64)>> = HashMap::new();
+    for s in wire {
+        let (line, span) = s.line_span();
+        lines.entry(line).or_default().push(span);
+    }
+
+    for spans in lines.values_mut() {
+        spans.sort_unstable();
+        let mut merged: Vec<(i64, i64)> = Vec::with_capacity(spans.len());
+        for &(lo, hi) in spans.iter() {
+            match merged.last_mut() {
+                Some(last) if lo <= last.1 + 1 => last.1 = last.1.max(hi),
+                _ => merged.push((lo, hi)),
+            }
+        }
+        *spans = merged;
+    }
+
+    lines
+}
+
+fn wire_segments(path: &[Path]) -> Vec<Segment> {
+    let mut curr = CENTRAL_PORT;
+    let mut steps = 0;
+    let mut segments = Vec::with_capacity(path.len());
 
     for p in path {
-        match p {
-            Path::Up(n) => {
-                for _ in 0..*n {
-                    curr.y += 1;
-                    points.push(Point {
-                        x: curr.x,
-                        y: curr.y,
-                    });
+        let ((dx, dy), n) = p.direction();
+        let end = Point {
+            x: curr.x + dx * i64::from(n),
+            y: curr.y + dy * i64::from(n),
+        };
+
+        segments.push(Segment {
+            start: curr,
+            end,
+            steps,
+        });
+
+        curr = end;
+        steps += i64::from(n);
+    }
+
+    segments
+}
+
+struct Crossing {
+    point: Point,
+    // combined steps both wires take to reach `point`
+    steps: i64,
+}
+
+#[derive(Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+enum Event {
+    Insert(usize),
+    Query(usize),
+    Remove(usize),
+}
+
+// Sweeps a vertical line left to right over the horizontal segments of one
+// wire and the vertical segments of the other. Horizontals are kept in a map
+// keyed by y while the line is over them, so each vertical only visits the
+// horizontals it actually crosses.
+fn perpendicular(horizontal: &[Segment], vertical: &[Segment]) -> Vec<Crossing> {
+    let mut events = Vec::with_capacity(2 * horizontal.len() + vertical.len());
+
+    for (i, h) in horizontal.iter().enumerate() {
+        let (lo, hi) = h.x_range();
+        events.push((lo, Event::Insert(i)));
+        events.push((hi, Event::Remove(i)));
+    }
+
+    for (i, v) in vertical.iter().enumerate() {
+        events.push((v.start.x, Event::Query(i)));
+    }
+
+    events.sort_unstable();
+
+    let mut active: BTreeMap<i64, Vec<usize>> = BTreeMap::new();
+    let mut crossings = Vec::new();
+
+    for (x, event) in events {
+        match event {
+            Event::Insert(i) => active.entry(horizontal[i].start.y).or_default().push(i),
+            Event::Remove(i) => {
+                let y = horizontal[i].start.y;
+                let row = active.get_mut(&y).unwrap();
+                row.retain(|&j| j != i);
+                if row.is_empty() {
+                    active.remove(&y);
                 }
             }
-            Path::Down(n) => {
-                for _ in 0..*n {
-                    curr.y -= 1;
-                    points.push(Point {
-                        x: curr.x,
-                        y: curr.y,
-                    });
+            Event::Query(i) => {
+                let v = &vertical[i];
+                let (lo, hi) = v.y_range();
+
+                for (&y, row) in active.range(lo..=hi) {
+                    let point = Point { x, y };
+                    for &j in row {
+                        crossings.push(Crossing {
+                            point,
+                            steps: horizontal[j].steps_to(point) + v.steps_to(point),
+                        });
+                    }
                 }
             }
-            Path::Left(n) => {
-                for _ in 0..*n {
-                    curr.x -= 1;
-                    points.push(Point {
-                        x: curr.x,
-                        y: curr.y,
-                    });
-                }
+        }
+    }
+
+    crossings
+}
+
+// Which points of a collinear overlap to report.
+#[derive(Clone, Copy)]
+enum Overlap {
+    Every,
+    // the ends and the point nearest the given origin
+    Extremes(Point),
+}
+
+// Segments lying on the same line share every point of their overlap. Every
+// metric and both step counts change monotonically away from the point
+// nearest an origin, so `Overlap::Extremes` only reports that point, the ends
+// and their neighbours (in case one of them is the central port itself).
+fn collinear(a: &[Segment], b: &[Segment], overlap: Overlap) -> Vec<Crossing> {
+    let mut lines: HashMap<(bool, i64), Vec<&Segment>> = HashMap::new();
+    for s in b.iter().filter(|s| !s.is_diagonal()) {
+        lines.entry(s.line()).or_default().push(s);
+    }
+
+    let mut crossings = Vec::new();
+
+    for s in a.iter().filter(|s| !s.is_diagonal()) {
+        let (horizontal, fixed) = s.line();
+        let (s_lo, s_hi) = s.span();
+
+        for t in lines.get(&s.line()).into_iter().flatten() {
+            let (t_lo, t_hi) = t.span();
+            let (lo, hi) = (s_lo.max(t_lo), s_hi.min(t_hi));
+            if lo > hi {
+                continue;
             }
-            Path::Right(n) => {
-                for _ in 0..*n {
-                    curr.x += 1;
-                    points.push(Point {
-                        x: curr.x,
-                        y: curr.y,
-                    });
+
+            let candidates: Vec<i64> = match overlap {
+                Overlap::Every => (lo..=hi).collect(),
+                Overlap::Extremes(origin) => {
+                    let along = if horizontal { origin.x } else { origin.y };
+                    let nearest = along.max(lo).min(hi);
+                    vec![lo, lo + 1, nearest - 1, nearest, nearest + 1, hi - 1, hi]
                 }
+            };
+
+            for c in candidates.into_iter().filter(|&c| lo <= c && c <= hi) {
+                let point = if horizontal {
+                    Point { x: c, y: fixed }
+                } else {
+                    Point { x: fixed, y: c }
+                };
+
+                crossings.push(Crossing {
+                    point,
+                    steps: s.steps_to(point) + t.steps_to(point),
+                });
             }
         }
     }
 
-    points
+    crossings
 }
 
-#[aoc(day3, part1)]
-pub fn solve_day1(input: &(Vec<Path>, Vec<Path>)) -> i32 {
-    let (path1, path2) = input;
-    let (path1, path2) = (path_points(&path1), path_points(&path2));
-    let intersection = intersection(&path1, &path2);
+// Lattice points shared by diagonal segment `d` and any other segment `t`.
+fn diagonal_points(d: &Segment, t: &Segment) -> Vec<Point> {
+    let (dx, dy) = d.direction();
+    let along = |k: i64| {
+        if 0 <= k && k <= d.len() {
+            Some(d.point(k))
+        } else {
+            None
+        }
+    };
+
+    let points: Vec<Point> = if t.start == t.end {
+        vec![t.start]
+    } else if t.is_horizontal() {
+        along((t.start.y - d.start.y) * dy).into_iter().collect()
+    } else if !t.is_diagonal() {
+        along((t.start.x - d.start.x) * dx).into_iter().collect()
+    } else {
+        // diagonals keep y - x constant when rising and y + x when falling
+        let invariant = |s: &Segment, p: Point| {
+            let (sx, sy) = s.direction();
+            p.y - sx * sy * p.x
+        };
+        let (tx, ty) = t.direction();
 
-    intersection
+        if dx * dy == tx * ty {
+            if invariant(d, d.start) == invariant(t, t.start) {
+                (0..=d.len()).map(|k| d.point(k)).collect()
+            } else {
+                vec![]
+            }
+        } else {
+            let (rising, falling) = if dx * dy == 1 { (d, t) } else { (t, d) };
+            let (a, b) = (
+                invariant(rising, rising.start),
+                invariant(falling, falling.start),
+            );
+
+            if (a + b) % 2 == 0 {
+                vec![Point {
+                    x: (b - a) / 2,
+                    y: (a + b) / 2,
+                }]
+            } else {
+                vec![]
+            }
+        }
+    };
+
+    points
         .into_iter()
-        .filter(|p| *p != Point { x: 0, y: 0 })
-        .map(|Point { x, y }| x + y)
-        .min()
-        .unwrap()
+        .filter(|&p| d.contains(p) && t.contains(p))
+        .collect()
 }
 
-fn steps_to(pt: Point, path: &Vec<Point>) -> i32 {
-    path.iter().position(|p| *p == pt).unwrap() as i32
+// The lattice point, if any, shared by two segments on lines of different
+// kinds.
+fn transversal_points(s: &Segment, t: &Segment) -> Vec<Point> {
+    if s.is_diagonal() {
+        diagonal_points(s, t)
+    } else if t.is_diagonal() {
+        diagonal_points(t, s)
+    } else {
+        let (h, v) = if s.is_horizontal() { (s, t) } else { (t, s) };
+        let point = Point {
+            x: v.start.x,
+            y: h.start.y,
+        };
+        if h.contains(point) && v.contains(point) {
+            vec![point]
+        } else {
+            vec![]
+        }
+    }
+}
+
+// Diagonal segments are an extension, so they are simply checked against
+// every segment of the other wire.
+fn diagonal(a: &[Segment], b: &[Segment]) -> Vec<Crossing> {
+    let mut crossings = Vec::new();
+
+    for s in a {
+        for t in b {
+            let d = if s.is_diagonal() {
+                s
+            } else if t.is_diagonal() {
+                t
+            } else {
+                continue;
+            };
+            let other = if std::ptr::eq(d, s) { t } else { s };
+
+            for point in diagonal_points(d, other) {
+                crossings.push(Crossing {
+                    point,
+                    steps: s.steps_to(point) + t.steps_to(point),
+                });
+            }
+        }
+    }
+
+    crossings
+}
+
+fn intersection(a: &[Segment], b: &[Segment], overlap: Overlap) -> Vec<Crossing> {
+    let straight = |w: &[Segment]| -> (Vec<Segment>, Vec<Segment>) {
+        w.iter()
+            .filter(|s| !s.is_diagonal())
+            .partition(|s| s.is_horizontal())
+    };
+    let (a_horizontal, a_vertical) = straight(a);
+    let (b_horizontal, b_vertical) = straight(b);
+
+    let mut crossings = perpendicular(&a_horizontal, &b_vertical);
+    crossings.extend(perpendicular(&b_horizontal, &a_vertical));
+    crossings.extend(collinear(a, b, overlap));
+    if a.iter().chain(b).any(Segment::is_diagonal) {
+        crossings.extend(diagonal(a, b));
+    }
+    crossings.retain(|c| c.point != CENTRAL_PORT);
+    crossings
+}
+
+// Steps along the wire to its first visit of `p`.
+fn steps_to(wire: &[Segment], p: Point) -> Option<i64> {
+    wire.iter().find(|s| s.contains(p)).map(|s| s.steps_to(p))
+}
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct PairCrossing {
+    pub wires: (usize, usize),
+    pub point: Point,
+    pub distance: f64,
+}
+
+// A wire coming back to a point it has already passed through.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct Revisit {
+    pub point: Point,
+    pub first: i64,
+    pub again: i64,
+}
+
+pub struct Panel {
+    wires: Vec<Vec<Segment>>,
+}
+
+impl Panel {
+    pub fn new(wires: &[Vec<Path>]) -> Panel {
+        Panel {
+            wires: wires.iter().map(|w| wire_segments(w)).collect(),
+        }
+    }
+
+    pub fn len(&self) -> usize {
+        self.wires.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.wires.is_empty()
+    }
+
+    fn pairs(&self) -> impl Iterator<Item = (usize, usize)> {
+        let n = self.wires.len();
+        (0..n).flat_map(move |i| (i + 1..n).map(move |j| (i, j)))
+    }
+
+    // The crossing between any two wires nearest to `from`.
+    pub fn closest_crossing(&self, from: Point, metric: Metric) -> Option<PairCrossing> {
+        self.pairs()
+            .flat_map(|(i, j)| {
+                intersection(&self.wires[i], &self.wires[j], Overlap::Extremes(from))
+                    .into_iter()
+                    .map(move |c| ((i, j), c.point))
+            })
+            .min_by_key(|&(_, point)| metric.key(from, point))
+            .map(|(wires, point)| PairCrossing {
+                wires,
+                point,
+                distance: metric.distance(from, point),
+            })
+    }
+
+    // Every point other than the central port that at least `k` different
+    // wires pass through, in ascending order. For `k` below 2 that is every
+    // point on any wire.
+    pub fn crossed_by_at_least(&self, k: usize) -> Vec<Point> {
+        if k < 2 {
+            let mut points: Vec<Point> = self
+                .wires
+                .iter()
+                .flat_map(|w| line_spans(w))
+                .flat_map(|(line, spans)| {
+                    spans
+                        .into_iter()
+                        .flat_map(move |(lo, hi)| (lo..=hi).map(move |t| line.point(t)))
+                })
+                .filter(|&p| p != CENTRAL_PORT)
+                .collect();
+            points.sort_unstable();
+            points.dedup();
+            return points;
+        }
+
+        let mut crossed: HashMap<Point, Vec<usize>> = HashMap::new();
+
+        for (i, j) in self.pairs() {
+            for c in intersection(&self.wires[i], &self.wires[j], Overlap::Every) {
+                let wires = crossed.entry(c.point).or_default();
+                wires.push(i);
+                wires.push(j);
+            }
+        }
+
+        let mut points: Vec<Point> = crossed
+            .into_iter()
+            .filter_map(|(p, mut wires)| {
+                wires.sort_unstable();
+                wires.dedup();
+                if wires.len() >= k {
+                    Some(p)
+                } else {
+                    None
+                }
+            })
+            .collect();
+
+        points.sort_unstable();
+        points
+    }
+
+    // Every time the wire comes back to a point it already passed through,
+    // in the order it happens. Retracing a segment revisits each of its points.
+    // Each segment is intersected with the ones before it rather than walked.
+    pub fn self_crossings(&self, wire: usize) -> Vec<Revisit> {
+        let wire = &self.wires[wire];
+        let mut revisits = Vec::new();
+
+        for (j, s) in wire.iter().enumerate() {
+            let (before, current) = (&wire[..j], std::slice::from_ref(s));
+            let mut points: Vec<Point> = intersection(before, current, Overlap::Every)
+                .into_iter()
+                .map(|c| c.point)
+                .collect();
+            if s.contains(CENTRAL_PORT) {
+                points.push(CENTRAL_PORT);
+            }
+
+            let mut found: Vec<Revisit> = points
+                .into_iter()
+                .filter(|&p| p != s.start)
+                .map(|point| Revisit {
+                    point,
+                    first: steps_to(before, point).unwrap_or(0),
+                    again: s.steps_to(point),
+                })
+                .collect();
+            found.sort_unstable_by_key(|r| r.again);
+            found.dedup();
+            revisits.extend(found);
+        }
+
+        revisits
+    }
+
+    pub fn first_revisit(&self, wire: usize) -> Option<Revisit> {
+        self.self_crossings(wire).into_iter().next()
+    }
+
+    // The number of distinct grid points the wire covers, its start included.
+    // Ranges are merged line by line, then points where lines of different
+    // kinds meet are taken off for every line past the first.
+    pub fn covered_area(&self, wire: usize) -> usize {
+        let wire = &self.wires[wire];
+        if wire.is_empty() {
+            return 1;
+        }
+
+        let covered: i64 = line_spans(wire)
+            .values()
+            .flatten()
+            .map(|(lo, hi)| hi - lo + 1)
+            .sum();
+
+        let mut shared: HashMap<Point, HashSet<Line>> = HashMap::new();
+        for (i, s) in wire.iter().enumerate() {
+            let (s_line, _) = s.line_span();
+            for t in &wire[i + 1..] {
+                let (t_line, _) = t.line_span();
+                if mem::discriminant(&s_line) == mem::discriminant(&t_line) {
+                    continue;
+                }
+                for p in transversal_points(s, t) {
+                    shared.entry(p).or_default().extend(&[s_line, t_line]);
+                }
+            }
+        }
+
+        let repeated: usize = shared.values().map(|lines| lines.len() - 1).sum();
+
+        covered as usize - repeated
+    }
+
+    // The lowest and highest corners of the box enclosing the wire.
+    pub fn bounding_box(&self, wire: usize) -> (Point, Point) {
+        let mut min = CENTRAL_PORT;
+        let mut max = CENTRAL_PORT;
+
+        for s in &self.wires[wire] {
+            min.x = min.x.min(s.end.x);
+            min.y = min.y.min(s.end.y);
+            max.x = max.x.max(s.end.x);
+            max.y = max.y.max(s.end.y);
+        }
+
+        (min, max)
+    }
+
+    // The fewest combined steps the given wires take to reach a point they all
+    // cross, or None unless at least two distinct wires of the panel are
+    // chosen.
+    pub fn fewest_combined_steps(&self, wires: &[usize]) -> Option<i64> {
+        let mut wires = wires.to_vec();
+        wires.sort_unstable();
+        wires.dedup();
+        if wires.len() < 2 || wires.iter().any(|&w| w >= self.wires.len()) {
+            return None;
+        }
+
+        let (first, second) = (&self.wires[wires[0]], &self.wires[wires[1]]);
+        let rest = &wires[2..];
+
+        let overlap = if rest.is_empty() {
+            Overlap::Extremes(CENTRAL_PORT)
+        } else {
+            Overlap::Every
+        };
+
+        intersection(first, second, overlap)
+            .into_iter()
+            .filter_map(|c| {
+                let others: Option<i64> = rest
+                    .iter()
+                    .map(|&w| steps_to(&self.wires[w], c.point))
+                    .sum();
+                others.map(|steps| c.steps + steps)
+            })
+            .min()
+    }
+}
+
+#[aoc(day3, part1)]
+pub fn solve_day1(input: &Panel) -> Result<i128, String> {
+    let metric = Metric::Manhattan;
+    input
+        .closest_crossing(CENTRAL_PORT, metric)
+        .map(|closest| metric.key(CENTRAL_PORT, closest.point))
+        .ok_or_else(|| String::from("the wires never cross"))
 }
 
 #[aoc(day3, part2)]
-pub fn solve_day2(input: &(Vec<Path>, Vec<Path>)) -> i32 {
-    let (path1, path2) = input;
-    let (path1, path2) = (path_points(&path1), path_points(&path2));
-    let intersections = intersection(&path1, &path2);
-
-    intersections
-        .iter()
-        .filter(|p| **p != Point { x: 0, y: 0 })
-        .map(|i| steps_to(*i, &path1) + steps_to(*i, &path2))
-        .min()
-        .unwrap()
+pub fn solve_day2(input: &Panel) -> Result<i64, String> {
+    let wires: Vec<usize> = (0..input.len()).collect();
+    input
+        .fewest_combined_steps(&wires)
+        .ok_or_else(|| String::from("the wires never all cross"))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const EXAMPLE_A: &str = "R8,U5,L5,D3\nU7,R6,D4,L4";
+    const EXAMPLE_B: &str = "R75,D30,R83,U83,L12,D49,R71,U7,L72\nU62,R66,U55,R34,D71,R55,D58,R83";
+    const EXAMPLE_C: &str =
+        "R98,U47,R26,D63,R33,U87,L62,D20,R33,U53,R51\nU98,R91,D20,R16,D67,R40,U7,R15,U6,R7";
+
+    #[test]
+    fn part1_a() {
+        assert_eq!(solve_day1(&input_generator(EXAMPLE_A).unwrap()), Ok(6));
+    }
+
+    #[test]
+    fn part1_b() {
+        assert_eq!(solve_day1(&input_generator(EXAMPLE_B).unwrap()), Ok(159));
+    }
+
+    #[test]
+    fn part1_c() {
+        assert_eq!(solve_day1(&input_generator(EXAMPLE_C).unwrap()), Ok(135));
+    }
+
+    #[test]
+    fn part2_a() {
+        assert_eq!(solve_day2(&input_generator(EXAMPLE_A).unwrap()), Ok(30));
+    }
+
+    #[test]
+    fn part2_b() {
+        assert_eq!(solve_day2(&input_generator(EXAMPLE_B).unwrap()), Ok(610));
+    }
+
+    #[test]
+    fn part2_c() {
+        assert_eq!(solve_day2(&input_generator(EXAMPLE_C).unwrap()), Ok(410));
+    }
+
+    #[test]
+    fn overlapping_wires() {
+        let input = input_generator("R5,U2\nR3,D4").unwrap();
+        assert_eq!(solve_day1(&input), Ok(1));
+        assert_eq!(solve_day2(&input), Ok(2));
+
+        let input = input_generator("U3,R4,U4\nR4,U5").unwrap();
+        assert_eq!(solve_day1(&input), Ok(7));
+        assert_eq!(solve_day2(&input), Ok(14));
+    }
+
+    #[test]
+    fn many_wires() {
+        let panel = input_generator("R8,U5,L5,D3\nU7,R6,D4,L4\nL2,U4,R10\nD1,R3,U7").unwrap();
+
+        let closest = panel
+            .closest_crossing(CENTRAL_PORT, Metric::Manhattan)
+            .unwrap();
+        assert_eq!(closest.wires, (0, 3));
+        assert_eq!(closest.point, Point { x: 3, y: 0 });
+        assert_eq!(closest.distance, 3.0);
+
+        assert_eq!(
+            panel.crossed_by_at_least(3),
+            vec![Point { x: 3, y: 3 }, Point { x: 3, y: 4 }]
+        );
+        assert!(panel.crossed_by_at_least(4).is_empty());
+
+        assert_eq!(panel.fewest_combined_steps(&[0, 1]), Some(30));
+        assert_eq!(
+            panel.fewest_combined_steps(&[3, 0, 1, 0]),
+            Some(20 + 20 + 8)
+        );
+        assert_eq!(panel.fewest_combined_steps(&[0, 2, 3]), Some(19 + 11 + 9));
+        assert_eq!(panel.fewest_combined_steps(&[0, 1, 2, 3]), None);
+        assert_eq!(panel.fewest_combined_steps(&[2, 2]), None);
+        assert_eq!(panel.fewest_combined_steps(&[]), None);
+        assert_eq!(panel.fewest_combined_steps(&[0, 4]), None);
+
+        let single = Panel::new(&[vec![Path::Right(8), Path::Up(5)]]);
+        assert!(solve_day2(&single).is_err());
+        assert!(solve_day1(&single).is_err());
+    }
+
+    #[test]
+    fn negative_quadrants() {
+        let input = input_generator("L5,D3,R2,U6\nD1,L6").unwrap();
+        assert_eq!(solve_day1(&input), Ok(4));
+    }
+
+    #[test]
+    fn metrics() {
+        let from = Point { x: 1, y: 2 };
+        let to = Point { x: 4, y: -2 };
+        assert_eq!(Metric::Manhattan.distance(from, to), 7.0);
+        assert_eq!(Metric::Chebyshev.distance(from, to), 4.0);
+        assert_eq!(Metric::Euclidean.distance(from, to), 5.0);
+
+        let panel = input_generator(EXAMPLE_A).unwrap();
+        let closest = |from, metric| panel.closest_crossing(from, metric).unwrap().point;
+        assert_eq!(
+            closest(CENTRAL_PORT, Metric::Euclidean),
+            Point { x: 3, y: 3 }
+        );
+        assert_eq!(
+            closest(Point { x: 7, y: 6 }, Metric::Manhattan),
+            Point { x: 6, y: 5 }
+        );
+        assert_eq!(
+            closest(Point { x: 2, y: 5 }, Metric::Chebyshev),
+            Point { x: 3, y: 3 }
+        );
+
+        // along an overlap the nearest crossing sits between its ends
+        let panel = input_generator("R10\nU1,R10,D1,L10").unwrap();
+        let closest = panel
+            .closest_crossing(Point { x: 4, y: -3 }, Metric::Euclidean)
+            .unwrap();
+        assert_eq!(closest.point, Point { x: 4, y: 0 });
+        assert_eq!(closest.distance, 3.0);
+    }
+
+    #[test]
+    fn parse_errors() {
+        assert_eq!(
+            input_generator("R8,U5\nU7,X6,D4").err(),
+            Some(String::from("line 2, column 4: unknown direction \"X\""))
+        );
+        assert_eq!(
+            input_generator("R8,,U5").err(),
+            Some(String::from("line 1, column 4: empty move"))
+        );
+        assert_eq!(
+            input_generator("R8, U-5").err(),
+            Some(String::from("line 1, column 5: invalid size \"-5\""))
+        );
+        assert_eq!(
+            input_generator("R8\n\n7").err(),
+            Some(String::from("line 3, column 1: missing direction in \"7\""))
+        );
+        assert_eq!(
+            input_generator("R8,UR2").err(),
+            Some(String::from("line 1, column 4: diagonal move \"UR2\""))
+        );
+        for input in &["", "\n\n", "R8,U5"] {
+            assert_eq!(
+                input_generator(input).err(),
+                Some(String::from("need at least two wires"))
+            );
+        }
+
+        let panel = input_generator("R8,U5\nL3,D4").unwrap();
+        assert!(solve_day1(&panel).is_err());
+        assert!(solve_day2(&panel).is_err());
+    }
+
+    #[test]
+    fn lowercase_and_whitespace() {
+        let panel = input_generator("  r8, u5 ,L5,d3 \n\nu7,R6, d4,l4\n").unwrap();
+        assert_eq!(panel.len(), 2);
+        assert_eq!(solve_day1(&panel), Ok(6));
+        assert_eq!(solve_day2(&panel), Ok(30));
+    }
+
+    #[test]
+    fn diagonals() {
+        let panel = parse_panel("UR4\nR4,UL4", true).unwrap();
+        assert_eq!(solve_day1(&panel), Ok(4));
+        assert_eq!(solve_day2(&panel), Ok(8));
+
+        // no lattice point where the diagonals cross
+        let panel = parse_panel("UR4\nR3,UL3", true).unwrap();
+        assert!(panel
+            .closest_crossing(CENTRAL_PORT, Metric::Manhattan)
+            .is_none());
+
+        let panel = parse_panel("ur4\nR1,U1,UR3", true).unwrap();
+        assert_eq!(solve_day1(&panel), Ok(2));
+        assert_eq!(solve_day2(&panel), Ok(3));
+        assert_eq!(panel.crossed_by_at_least(2).len(), 4);
+    }
+
+    #[test]
+    fn crossed_by_one_wire() {
+        let panel = input_generator("R2,U1\nU1,R2").unwrap();
+        let points: Vec<(i64, i64)> = panel
+            .crossed_by_at_least(1)
+            .iter()
+            .map(|p| (p.x, p.y))
+            .collect();
+        assert_eq!(points, [(0, 1), (1, 0), (1, 1), (2, 0), (2, 1)]);
+        assert_eq!(panel.crossed_by_at_least(0), panel.crossed_by_at_least(1));
+        assert_eq!(panel.crossed_by_at_least(2), [Point { x: 2, y: 1 }]);
+    }
+
+    #[test]
+    fn long_moves() {
+        let panel = input_generator("R3000000000\nU1,R1,D2").unwrap();
+        let closest = panel
+            .closest_crossing(CENTRAL_PORT, Metric::Manhattan)
+            .unwrap();
+        assert_eq!(closest.point, Point { x: 1, y: 0 });
+        assert_eq!(closest.distance, 1.0);
+
+        let panel =
+            input_generator("R4000000000,R4000000000,U1\nU1,R4000000000,R4000000000,D1").unwrap();
+        assert_eq!(solve_day1(&panel), Ok(8_000_000_000));
+        assert_eq!(solve_day2(&panel), Ok(16_000_000_002));
+        assert_eq!(
+            Metric::Euclidean.distance(
+                CENTRAL_PORT,
+                Point {
+                    x: 4_000_000_000,
+                    y: 3_000_000_000
+                }
+            ),
+            5e9
+        );
+
+        // past 2^53 the distance as an f64 is no longer exact
+        let far = (1 << 53) + 1;
+        let segment = |start: Point, end: Point, steps| Segment { start, end, steps };
+        let panel = Panel {
+            wires: vec![
+                vec![segment(CENTRAL_PORT, Point { x: far, y: 0 }, 0)],
+                vec![
+                    segment(CENTRAL_PORT, Point { x: 0, y: 1 }, 0),
+                    segment(Point { x: 0, y: 1 }, Point { x: far, y: 1 }, 1),
+                    segment(Point { x: far, y: 1 }, Point { x: far, y: -1 }, far + 1),
+                ],
+            ],
+        };
+        assert_eq!(solve_day1(&panel), Ok(i128::from(far)));
+        assert_eq!(solve_day2(&panel), Ok(2 * far + 2));
+    }
+
+    #[test]
+    fn self_crossings() {
+        let panel = input_generator("R8,U5,L5,D3\nR4,U2,L2,D4\nR3,L5").unwrap();
+
+        assert!(panel.self_crossings(0).is_empty());
+        assert_eq!(panel.first_revisit(0), None);
+        assert_eq!(panel.covered_area(0), 22);
+        assert_eq!(panel.bounding_box(0), (CENTRAL_PORT, Point { x: 8, y: 5 }));
+
+        let crossing = Revisit {
+            point: Point { x: 2, y: 0 },
+            first: 2,
+            again: 10,
+        };
+        assert_eq!(panel.self_crossings(1), vec![crossing]);
+        assert_eq!(panel.first_revisit(1), Some(crossing));
+        assert_eq!(panel.covered_area(1), 12);
+        assert_eq!(
+            panel.bounding_box(1),
+            (Point { x: 0, y: -2 }, Point { x: 4, y: 2 })
+        );
+
+        let retraced: Vec<(i64, i64)> = panel
+            .self_crossings(2)
+            .iter()
+            .map(|r| (r.point.x, r.again))
+            .collect();
+        assert_eq!(retraced, vec![(2, 4), (1, 5), (0, 6)]);
+        assert_eq!(panel.covered_area(2), 6);
+    }
+
+    // Every point of a wire with the steps taken to reach it, starting at the
+    // central port.
+    fn walk(panel: &Panel, wire: usize) -> Vec<(Point, i64)> {
+        let segments = panel.wires[wire]
+            .iter()
+            .flat_map(|s| (1..=s.len()).map(move |k| (s.point(k), s.steps + k)));
+        std::iter::once((CENTRAL_PORT, 0)).chain(segments).collect()
+    }
+
+    #[test]
+    fn segments_match_walking() {
+        let mut rng = crate::xorshift::XorShift::new(3);
+        let moves = ["U", "D", "L", "R", "UL", "UR", "DL", "DR"];
+
+        for _ in 0..200 {
+            let wires: Vec<String> = (0..2)
+                .map(|_| {
+                    let n = 1 + rng.below(8);
+                    let moves: Vec<String> = (0..n)
+                        .map(|_| format!("{}{}", moves[rng.below(8)], rng.below(6)))
+                        .collect();
+                    moves.join(",")
+                })
+                .collect();
+            let panel = parse_panel(&wires.join("\n"), true).unwrap();
+
+            let mut points = Vec::new();
+            for (wire, path) in wires.iter().enumerate() {
+                let steps = walk(&panel, wire);
+                let mut seen = HashMap::new();
+                let mut revisits = Vec::new();
+                for &(point, again) in &steps {
+                    let first = *seen.entry(point).or_insert(again);
+                    if first != again {
+                        revisits.push(Revisit {
+                            point,
+                            first,
+                            again,
+                        });
+                    }
+                }
+
+                assert_eq!(panel.self_crossings(wire), revisits, "{}", path);
+                assert_eq!(panel.covered_area(wire), seen.len(), "{}", path);
+                points.extend(seen.keys().filter(|&&p| p != CENTRAL_PORT));
+            }
+
+            points.sort_unstable();
+            points.dedup();
+            assert_eq!(panel.crossed_by_at_least(1), points);
+        }
+    }
+
+    #[test]
+    fn long_self_crossings() {
+        let panel = input_generator(
+            "R3000000000,U1,L1,D2
+R3000000000,L3000000000",
+        )
+        .unwrap();
+        assert_eq!(
+            panel.first_revisit(0),
+            Some(Revisit {
+                point: Point {
+                    x: 2_999_999_999,
+                    y: 0
+                },
+                first: 2_999_999_999,
+                again: 3_000_000_003,
+            })
+        );
+        assert_eq!(panel.covered_area(0), 3_000_000_004);
+        assert_eq!(panel.covered_area(1), 3_000_000_001);
+    }
 }
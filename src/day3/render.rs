@@ -0,0 +1,265 @@
+use std::env;
+use std::fmt::Write;
+
+use super::{intersection, Metric, Overlap, Panel, Point, Segment, CENTRAL_PORT};
+
+const COLORS: [&str; 6] = [
+    "#1f77b4", "#ff7f0e", "#2ca02c", "#9467bd", "#8c564b", "#e377c2",
+];
+
+struct Bounds {
+    min: Point,
+    max: Point,
+}
+
+impl Bounds {
+    fn of(panel: &Panel) -> Bounds {
+        let mut bounds = Bounds {
+            min: CENTRAL_PORT,
+            max: CENTRAL_PORT,
+        };
+
+        for s in panel.wires.iter().flatten() {
+            for p in &[s.start, s.end] {
+                bounds.min.x = bounds.min.x.min(p.x);
+                bounds.min.y = bounds.min.y.min(p.y);
+                bounds.max.x = bounds.max.x.max(p.x);
+                bounds.max.y = bounds.max.y.max(p.y);
+            }
+        }
+
+        bounds
+    }
+
+    fn width(&self) -> usize {
+        (self.max.x - self.min.x) as usize + 1
+    }
+
+    fn height(&self) -> usize {
+        (self.max.y - self.min.y) as usize + 1
+    }
+}
+
+fn crossings(panel: &Panel) -> Vec<Point> {
+    let mut points: Vec<Point> = panel
+        .pairs()
+        .flat_map(|(i, j)| {
+            intersection(
+                &panel.wires[i],
+                &panel.wires[j],
+                Overlap::Extremes(CENTRAL_PORT),
+            )
+        })
+        .map(|c| c.point)
+        .collect();
+
+    points.sort_unstable();
+    points.dedup();
+    points
+}
+
+fn winner(panel: &Panel) -> Option<Point> {
+    panel
+        .closest_crossing(CENTRAL_PORT, Metric::Manhattan)
+        .map(|c| c.point)
+}
+
+// The size of the terminal according to `COLUMNS` and `LINES`, falling back
+// to 80x24 when they aren't exported.
+pub fn terminal_size() -> (usize, usize) {
+    let var = |name, default| {
+        env::var(name)
+            .ok()
+            .and_then(|v| v.parse().ok())
+            .unwrap_or(default)
+    };
+    (var("COLUMNS", 80), var("LINES", 24))
+}
+
+struct Canvas {
+    bounds: Bounds,
+    scale: usize,
+    columns: usize,
+    cells: Vec<Vec<char>>,
+}
+
+impl Canvas {
+    fn new(bounds: Bounds, columns: usize, rows: usize) -> Canvas {
+        let fit = |extent: usize, cells: usize| extent.div_ceil(cells.max(1));
+        let scale = fit(bounds.width(), columns)
+            .max(fit(bounds.height(), rows))
+            .max(1);
+
+        let columns = (bounds.width() - 1) / scale + 1;
+        let rows = (bounds.height() - 1) / scale + 1;
+
+        Canvas {
+            bounds,
+            scale,
+            columns,
+            cells: vec![vec!['.'; columns]; rows],
+        }
+    }
+
+    fn cell(&self, p: Point) -> (usize, usize) {
+        let column = (p.x - self.bounds.min.x) as usize / self.scale;
+        let row = (self.bounds.max.y - p.y) as usize / self.scale;
+        (column, row)
+    }
+
+    fn set(&mut self, p: Point, c: char) {
+        let (column, row) = self.cell(p);
+        self.cells[row][column] = c;
+    }
+
+    fn segment(&mut self, s: &Segment) {
+        let (c0, r0) = self.cell(s.start);
+        let (c1, r1) = self.cell(s.end);
+
+        if s.is_diagonal() {
+            let (dx, dy) = s.direction();
+            let c = if dx == dy { '/' } else { '\\' };
+            for k in 0..=s.len() {
+                self.set(s.point(k), c);
+            }
+        } else if s.is_horizontal() {
+            for column in c0.min(c1)..=c0.max(c1) {
+                self.cells[r0][column] = '-';
+            }
+        } else {
+            for row in r0.min(r1)..=r0.max(r1) {
+                self.cells[row][c0] = '|';
+            }
+        }
+    }
+}
+
+// Draws the wires in at most `columns` x `rows` characters, scaling the
+// panel down evenly when it doesn't fit. The central port is `o`, crossings
+// are `X` and the crossing nearest the port is `*`.
+pub fn ascii(panel: &Panel, columns: usize, rows: usize) -> String {
+    let mut canvas = Canvas::new(Bounds::of(panel), columns, rows);
+
+    for wire in &panel.wires {
+        for s in wire {
+            canvas.segment(s);
+        }
+        for s in wire.iter().skip(1) {
+            canvas.set(s.start, '+');
+        }
+    }
+
+    for p in crossings(panel) {
+        canvas.set(p, 'X');
+    }
+    if let Some(p) = winner(panel) {
+        canvas.set(p, '*');
+    }
+    canvas.set(CENTRAL_PORT, 'o');
+
+    let mut out = String::with_capacity(canvas.cells.len() * (canvas.columns + 1));
+    for row in canvas.cells {
+        out.extend(row);
+        out.push('\n');
+    }
+    out
+}
+
+// Draws the wires as an SVG document in panel coordinates, with y pointing
+// up. Strokes and markers keep a constant on-screen size however large the
+// panel is.
+pub fn svg(panel: &Panel) -> String {
+    let bounds = Bounds::of(panel);
+    let margin = (bounds.width().max(bounds.height()) / 50).max(1) as i64;
+    let marker = margin as f64 / 2.0;
+    let mut out = String::new();
+
+    writeln!(
+        out,
+        r#"<svg xmlns="http://www.w3.org/2000/svg" viewBox="{} {} {} {}">"#,
+        bounds.min.x - margin,
+        -bounds.max.y - margin,
+        bounds.width() as i64 - 1 + 2 * margin,
+        bounds.height() as i64 - 1 + 2 * margin
+    )
+    .unwrap();
+
+    for (i, wire) in panel.wires.iter().enumerate() {
+        let mut points = format!("{},{}", CENTRAL_PORT.x, -CENTRAL_PORT.y);
+        for s in wire {
+            write!(points, " {},{}", s.end.x, -s.end.y).unwrap();
+        }
+
+        writeln!(
+            out,
+            r#"  <polyline points="{}" fill="none" stroke="{}" stroke-width="1.5" vector-effect="non-scaling-stroke"/>"#,
+            points,
+            COLORS[i % COLORS.len()]
+        )
+        .unwrap();
+    }
+
+    let mut circle = |p: Point, r: f64, fill: &str| {
+        writeln!(
+            out,
+            r#"  <circle cx="{}" cy="{}" r="{}" fill="{}"/>"#,
+            p.x, -p.y, r, fill
+        )
+        .unwrap();
+    };
+
+    for p in crossings(panel) {
+        circle(p, marker / 2.0, "black");
+    }
+    if let Some(p) = winner(panel) {
+        circle(p, marker, "red");
+    }
+    circle(CENTRAL_PORT, marker, "black");
+
+    out.push_str("</svg>\n");
+    out
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::day3::input_generator;
+
+    const EXAMPLE: &str = "R8,U5,L5,D3\nU7,R6,D4,L4";
+
+    #[test]
+    fn full_size() {
+        let expected = "\
++-----+..
+|.....|..
+|..+--X-+
+|..|..|.|
+|.-*--+.|
+|..|....|
+|.......|
+o-------+
+";
+        assert_eq!(ascii(&input_generator(EXAMPLE).unwrap(), 80, 24), expected);
+    }
+
+    #[test]
+    fn scaled_down() {
+        let expected = "\
++--+.
+|+-X+
+|*-+|
+o---+
+";
+        assert_eq!(ascii(&input_generator(EXAMPLE).unwrap(), 5, 5), expected);
+    }
+
+    #[test]
+    fn svg_document() {
+        let svg = svg(&input_generator(EXAMPLE).unwrap());
+        assert!(svg.starts_with("<svg"));
+        assert!(svg.contains(r#"points="0,0 8,0 8,-5 3,-5 3,-2""#));
+        assert!(svg.contains(r#"<circle cx="3" cy="-3" r="0.5" fill="red"/>"#));
+        assert_eq!(svg.matches("<circle").count(), 4);
+        assert!(svg.ends_with("</svg>\n"));
+    }
+}
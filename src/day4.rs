@@ -1,71 +1,442 @@
+use std::fmt;
+
+use crate::input::error_at;
+
+// The puzzle range as `lo-hi` on a line of its own, both ends included.
 #[aoc_generator(day4)]
-pub fn input_generator(_: &str) -> (i32, i32) {
-    (359282, 820401)
+pub fn input_generator(input: &str) -> Result<(u64, u64), String> {
+    let mut lines = input
+        .lines()
+        .enumerate()
+        .filter(|(_, line)| !line.trim().is_empty());
+
+    let (row, line) = lines.next().ok_or("missing range")?;
+    if let Some((extra, extra_line)) = lines.next() {
+        let indent = extra_line.len() - extra_line.trim_start().len();
+        return Err(error_at(
+            extra,
+            extra_line,
+            indent,
+            "expected a single range",
+        ));
+    }
+
+    let at = |offset: usize, msg: String| error_at(row, line, offset, msg);
+    let indent = line.len() - line.trim_start().len();
+
+    let (lo, hi) = match line.trim().split('-').collect::<Vec<_>>()[..] {
+        [lo, hi] => (lo, hi),
+        _ => {
+            let msg = format!("expected \"lo-hi\", got \"{}\"", line.trim());
+            return Err(at(indent, msg));
+        }
+    };
+
+    let bound = |s: &str, offset: usize| {
+        let offset = offset + s.len() - s.trim_start().len();
+        s.trim()
+            .parse::<u64>()
+            .map_err(|_| at(offset, format!("invalid bound \"{}\"", s.trim())))
+    };
+    let (lo, hi) = (bound(lo, indent)?, bound(hi, indent + lo.len() + 1)?);
+
+    if lo > hi {
+        return Err(at(indent, format!("empty range {}-{}", lo, hi)));
+    }
+
+    Ok((lo, hi))
 }
 
-#[aoc(day4, part1)]
-pub fn solve_part1(bounds: &(i32, i32)) -> i32 {
-    let (min, max) = bounds;
+fn digit_count(n: u64) -> usize {
+    n.to_string().len()
+}
+
+// Lengths of the runs of equal digits, left to right.
+fn runs(digits: &[u8]) -> Vec<usize> {
+    let mut runs: Vec<usize> = Vec::new();
+    for (i, d) in digits.iter().enumerate() {
+        if i > 0 && digits[i - 1] == *d {
+            *runs.last_mut().unwrap() += 1;
+        } else {
+            runs.push(1);
+        }
+    }
+    runs
+}
+
+struct Search<'a> {
+    lo: u128,
+    hi: u128,
+    len: usize,
+    accept: &'a dyn Fn(&[u8]) -> bool,
+    digits: Vec<u8>,
+}
+
+impl Search<'_> {
+    // Extends the digits so far with every digit no smaller than the last,
+    // skipping prefixes whose completions all fall outside the range.
+    fn count(&mut self, value: u128) -> usize {
+        if self.digits.len() == self.len {
+            let inside = self.lo <= value && value <= self.hi;
+            return (inside && (self.accept)(&self.digits)) as usize;
+        }
 
-    let adjacent_digits = |s: &str| {
-        let a = s.chars();
-        let mut b = s.chars();
-        b.next();
+        let rest = (self.len - self.digits.len() - 1) as u32;
+        let scale = 10u128.pow(rest);
+        let first = if self.len == 1 { 0 } else { 1 };
+        let from = self.digits.last().copied().unwrap_or(first);
+        let mut total = 0;
 
-        for (c, d) in a.zip(b) {
-            if c == d {
-                return true;
+        for d in from..=9 {
+            let prefix = (value * 10 + d as u128) * scale;
+            let lowest = prefix + d as u128 * (scale - 1) / 9;
+            let highest = prefix + scale - 1;
+
+            if highest < self.lo {
+                continue;
+            }
+            if lowest > self.hi {
+                break;
             }
+
+            self.digits.push(d);
+            total += self.count(value * 10 + d as u128);
+            self.digits.pop();
         }
 
-        false
-    };
+        total
+    }
+}
 
-    (*min..*max)
-        .map(|i| i.to_string())
-        .filter(|s| s.chars().is_sorted())
-        .filter(|s| adjacent_digits(s))
-        .count() as i32
+fn search(lo: u64, hi: u64, accept: &dyn Fn(&[u8]) -> bool) -> usize {
+    if lo > hi {
+        return 0;
+    }
+
+    (digit_count(lo)..=digit_count(hi))
+        .map(|len| {
+            Search {
+                lo: lo as u128,
+                hi: hi as u128,
+                len,
+                accept,
+                digits: Vec::with_capacity(len),
+            }
+            .count(0)
+        })
+        .sum()
 }
 
-#[aoc(day4, part2)]
-pub fn solve_part2(bounds: &(i32, i32)) -> i32 {
-    let (min, max) = bounds;
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum Rule {
+    NonDecreasing,
+    RunOfExactly(usize),
+    RunOfAtLeast(usize),
+    Digits(usize),
+    Range(u64, u64),
+    All(Vec<Rule>),
+    Any(Vec<Rule>),
+    Not(Box<Rule>),
+}
 
-    let adjacent_digits = |s: &str| {
-        let mut iter = s.chars();
+fn digits(n: u64) -> Vec<u8> {
+    n.to_string().bytes().map(|b| b - b'0').collect()
+}
 
-        let a = iter.next().unwrap();
-        let b = iter.next().unwrap();
-        let c = iter.next().unwrap();
-        let d = iter.next().unwrap();
-        let e = iter.next().unwrap();
-        let f = iter.next().unwrap();
+impl Rule {
+    pub fn and(self, other: Rule) -> Rule {
+        match self {
+            Rule::All(mut rules) => {
+                rules.push(other);
+                Rule::All(rules)
+            }
+            rule => Rule::All(vec![rule, other]),
+        }
+    }
 
-        if (a == b) && (b != c) {
-            return true;
+    pub fn or(self, other: Rule) -> Rule {
+        match self {
+            Rule::Any(mut rules) => {
+                rules.push(other);
+                Rule::Any(rules)
+            }
+            rule => Rule::Any(vec![rule, other]),
         }
-        if (b == c) && (a != b) && (c != d) {
-            return true;
+    }
+
+    fn matches_digits(&self, digits: &[u8]) -> bool {
+        match self {
+            Rule::NonDecreasing => digits.windows(2).all(|w| w[0] <= w[1]),
+            Rule::RunOfExactly(n) => runs(digits).contains(n),
+            Rule::RunOfAtLeast(n) => runs(digits).iter().any(|r| r >= n),
+            Rule::Digits(n) => digits.len() == *n,
+            Rule::Range(lo, hi) => {
+                let value = digits.iter().fold(0, |v, &d| v * 10 + d as u128);
+                *lo as u128 <= value && value <= *hi as u128
+            }
+            Rule::All(rules) => rules.iter().all(|r| r.matches_digits(digits)),
+            Rule::Any(rules) => rules.iter().any(|r| r.matches_digits(digits)),
+            Rule::Not(rule) => !rule.matches_digits(digits),
         }
-        if (c == d) && (b != c) && (d != e) {
-            return true;
+    }
+
+    pub fn matches(&self, n: u64) -> bool {
+        self.matches_digits(&digits(n))
+    }
+
+    // The innermost rule that rejects `n`. Only a failed `Any` or `Not` is
+    // reported as a whole, since none of its parts is to blame on its own.
+    pub fn check(&self, n: u64) -> Result<(), &Rule> {
+        let digits = digits(n);
+        self.check_digits(&digits)
+    }
+
+    fn check_digits(&self, digits: &[u8]) -> Result<(), &Rule> {
+        match self {
+            Rule::All(rules) => rules.iter().try_for_each(|r| r.check_digits(digits)),
+            rule if rule.matches_digits(digits) => Ok(()),
+            rule => Err(rule),
         }
+    }
 
-        if (d == e) && (c != d) && (e != f) {
-            return true;
+    // Whether every match has digits that never decrease.
+    fn requires_non_decreasing(&self) -> bool {
+        match self {
+            Rule::NonDecreasing => true,
+            Rule::All(rules) => rules.iter().any(Rule::requires_non_decreasing),
+            Rule::Any(rules) => rules.iter().all(Rule::requires_non_decreasing),
+            _ => false,
         }
+    }
 
-        if (e == f) && (d != e) {
-            return true;
+    // Counts the numbers in `lo..=hi` that match. Rules that require digits
+    // never to decrease only need those numbers searched, which is fast;
+    // any other rule is checked against every number in the range.
+    pub fn count(&self, lo: u64, hi: u64) -> usize {
+        if self.requires_non_decreasing() {
+            search(lo, hi, &|digits| self.matches_digits(digits))
+        } else {
+            (lo..=hi).filter(|&n| self.matches(n)).count()
         }
+    }
+}
 
-        false
-    };
+impl std::ops::Not for Rule {
+    type Output = Rule;
+
+    fn not(self) -> Rule {
+        match self {
+            Rule::Not(rule) => *rule,
+            rule => Rule::Not(Box::new(rule)),
+        }
+    }
+}
+
+impl fmt::Display for Rule {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let join = |rules: &[Rule], op| {
+            let rules: Vec<String> = rules.iter().map(|r| format!("({})", r)).collect();
+            rules.join(op)
+        };
+
+        match self {
+            Rule::NonDecreasing => write!(f, "digits never decrease"),
+            Rule::RunOfExactly(n) => write!(f, "has a run of exactly {} equal digits", n),
+            Rule::RunOfAtLeast(n) => write!(f, "has a run of at least {} equal digits", n),
+            Rule::Digits(n) => write!(f, "has {} digits", n),
+            Rule::Range(lo, hi) => write!(f, "lies within {}-{}", lo, hi),
+            Rule::All(rules) => write!(f, "{}", join(rules, " and ")),
+            Rule::Any(rules) => write!(f, "{}", join(rules, " or ")),
+            Rule::Not(rule) => write!(f, "not ({})", rule),
+        }
+    }
+}
+
+pub fn part1_rule(lo: u64, hi: u64) -> Rule {
+    Rule::Range(lo, hi)
+        .and(Rule::NonDecreasing)
+        .and(Rule::RunOfAtLeast(2))
+}
+
+pub fn part2_rule(lo: u64, hi: u64) -> Rule {
+    part1_rule(lo, hi).and(Rule::RunOfExactly(2))
+}
+
+#[aoc(day4, part1)]
+pub fn solve_part1(bounds: &(u64, u64)) -> usize {
+    let (lo, hi) = *bounds;
+    part1_rule(lo, hi).count(lo, hi)
+}
+
+#[aoc(day4, part2)]
+pub fn solve_part2(bounds: &(u64, u64)) -> usize {
+    let (lo, hi) = *bounds;
+    part2_rule(lo, hi).count(lo, hi)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // A check on the lengths of the runs of equal digits in a number.
+    type Runs = dyn Fn(&[usize]) -> bool;
+
+    // The numbers in `lo..=hi` whose digits never decrease and whose runs of
+    // equal digits satisfy `accept`, counted one by one.
+    fn brute_force(lo: u64, hi: u64, accept: &Runs) -> usize {
+        (lo..=hi)
+            .map(|n| n.to_string().bytes().map(|b| b - b'0').collect::<Vec<u8>>())
+            .filter(|d| d.windows(2).all(|w| w[0] <= w[1]))
+            .filter(|d| accept(&runs(d)))
+            .count()
+    }
+
+    #[test]
+    fn ranges() {
+        let error = |input| input_generator(input).err().unwrap();
+        assert_eq!(input_generator("359282-820401"), Ok((359282, 820401)));
+        assert_eq!(input_generator(" 10 - 20 \n\n"), Ok((10, 20)));
+        assert_eq!(input_generator("7-7"), Ok((7, 7)));
+        assert_eq!(
+            error("359282"),
+            "line 1, column 1: expected \"lo-hi\", got \"359282\""
+        );
+        assert_eq!(
+            error("\n  1-2-3"),
+            "line 2, column 3: expected \"lo-hi\", got \"1-2-3\""
+        );
+        assert_eq!(error("1- x"), "line 1, column 4: invalid bound \"x\"");
+        assert_eq!(error("20-10"), "line 1, column 1: empty range 20-10");
+        assert_eq!(
+            error("1-2\n3-4"),
+            "line 2, column 1: expected a single range"
+        );
+        assert_eq!(
+            error("1-2\n\n  3-4"),
+            "line 3, column 3: expected a single range"
+        );
+        assert_eq!(error(" \n"), "missing range");
+    }
+
+    #[test]
+    fn inclusive_bounds() {
+        assert_eq!(solve_part1(&(111111, 111111)), 1);
+        assert_eq!(solve_part1(&(111119, 111122)), 2);
+        assert_eq!(solve_part2(&(112233, 112233)), 1);
+        assert_eq!(solve_part2(&(123444, 123444)), 0);
+        assert_eq!(solve_part2(&(111122, 111122)), 1);
+    }
+
+    #[test]
+    fn rules() {
+        let rule = part1_rule(100000, 999999);
+        assert!(rule.matches(111111));
+        assert_eq!(rule.check(223450), Err(&Rule::NonDecreasing));
+        assert_eq!(rule.check(123789), Err(&Rule::RunOfAtLeast(2)));
+        assert_eq!(rule.check(1111111), Err(&Rule::Range(100000, 999999)));
+        let six = Rule::Digits(6).and(rule);
+        assert_eq!(six.check(11111), Err(&Rule::Digits(6)));
+
+        let rule = part2_rule(100000, 999999);
+        assert_eq!(rule.check(112233), Ok(()));
+        assert_eq!(rule.check(123444), Err(&Rule::RunOfExactly(2)));
+        assert_eq!(rule.check(111122), Ok(()));
+
+        let either = Rule::RunOfExactly(3).or(Rule::RunOfExactly(5));
+        let rule = Rule::NonDecreasing
+            .and(either.clone())
+            .and(!Rule::Range(0, 9999));
+        assert_eq!(rule.check(11123), Ok(()));
+        assert_eq!(rule.check(11223), Err(&either));
+        assert_eq!(rule.check(1112), Err(&!Rule::Range(0, 9999)));
+        assert!(rule.matches(22222333334));
+        assert_eq!(!!Rule::NonDecreasing, Rule::NonDecreasing);
+
+        assert_eq!(
+            rule.to_string(),
+            "(digits never decrease) and ((has a run of exactly 3 equal digits) or \
+             (has a run of exactly 5 equal digits)) and (not (lies within 0-9999))"
+        );
+    }
+
+    #[test]
+    fn rules_count_like_the_solvers() {
+        let (lo, hi) = (123456, 654321);
+        assert_eq!(part1_rule(lo, hi).count(lo, hi), solve_part1(&(lo, hi)));
+        assert_eq!(part2_rule(lo, hi).count(0, 999_999), solve_part2(&(lo, hi)));
+
+        let rule = Rule::NonDecreasing.and(!Rule::RunOfAtLeast(3));
+        let accept: &Runs = &|runs| runs.iter().all(|&r| r < 3);
+        assert_eq!(rule.count(0, 200_000), brute_force(0, 200_000, accept));
+        for n in 0..2000 {
+            assert_eq!(rule.matches(n), brute_force(n, n, accept) == 1);
+        }
+    }
+
+    #[test]
+    fn count_matches_brute_force() {
+        assert_eq!(Rule::Digits(2).count(10, 99), 90);
+        assert_eq!((!Rule::NonDecreasing).count(10, 99), 45);
+
+        let rules = [
+            Rule::RunOfAtLeast(2),
+            Rule::Digits(3).or(Rule::NonDecreasing),
+            Rule::NonDecreasing.or(Rule::RunOfExactly(2)),
+            !Rule::NonDecreasing.and(Rule::RunOfExactly(2)),
+            Rule::RunOfAtLeast(2).and(Rule::NonDecreasing),
+            Rule::NonDecreasing
+                .and(Rule::Digits(3))
+                .or(Rule::NonDecreasing),
+        ];
+        for rule in &rules {
+            let expected = (0..=2000).filter(|&n| rule.matches(n)).count();
+            assert_eq!(rule.count(0, 2000), expected, "{}", rule);
+        }
+        assert_eq!(rules[0].count(0, 99), 9);
+    }
+
+    #[test]
+    fn runs_of_digits() {
+        assert_eq!(runs(&[1, 1, 2, 2, 2, 3]), [2, 3, 1]);
+        assert_eq!(runs(&[4]), [1]);
+    }
+
+    #[test]
+    fn matches_brute_force() {
+        let rules: [(Rule, &Runs); 3] = [
+            (Rule::NonDecreasing, &|_| true),
+            (Rule::RunOfAtLeast(2), &|runs| runs.iter().any(|&r| r >= 2)),
+            (Rule::RunOfExactly(2), &|runs| runs.contains(&2)),
+        ];
+
+        for (rule, accept) in &rules {
+            let rule = Rule::NonDecreasing.and(rule.clone());
+            for &(lo, hi) in &[
+                (0, 0),
+                (0, 9),
+                (0, 150_000),
+                (1234, 98765),
+                (111, 111),
+                (50, 40),
+            ] {
+                assert_eq!(rule.count(lo, hi), brute_force(lo, hi, *accept));
+            }
+        }
+    }
+
+    #[test]
+    fn many_digits() {
+        // zero and every non-decreasing sequence of 1 to 12 digits from 1-9
+        let rule = Rule::NonDecreasing;
+        let all = rule.count(0, 10u64.pow(12) - 1);
+        assert_eq!(all, 1 + (1..=12).map(|n| binomial(n + 8, 8)).sum::<usize>());
+
+        let ones = 11_111_111_111_111_111_111;
+        assert_eq!(rule.count(ones, ones + 8), 9);
+        assert_eq!(rule.count(u64::MAX - 10u64.pow(17), u64::MAX), 0);
+    }
 
-    (*min..*max)
-        .map(|i| i.to_string())
-        .filter(|s| s.chars().is_sorted())
-        .filter(|s| adjacent_digits(s))
-        .count() as i32
+    fn binomial(n: usize, k: usize) -> usize {
+        (0..k).fold(1, |acc, i| acc * (n - i) / (i + 1))
+    }
 }
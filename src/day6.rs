@@ -1,50 +1,448 @@
 use std::collections::HashMap;
 use std::collections::HashSet;
 
-pub type Tree = HashMap<String, String>;
+use crate::input::error_at;
 
-#[aoc_generator(day6)]
-pub fn input_generator(input: &str) -> Tree {
-    input
-        .lines()
-        .map(|l| l.split(')'))
-        .map(|mut i| (i.next().unwrap().to_string(), i.next().unwrap().to_string()))
-        .map(|(p, c)| (c, p))
-        .collect()
+pub mod render;
+
+// Bodies are interned and referred to by their index in the map.
+pub type Body = usize;
+
+pub struct Tree {
+    names: Vec<String>,
+    ids: HashMap<String, Body>,
+    parents: Vec<Option<Body>>,
+    children: Vec<Vec<Body>>,
+    depths: Vec<usize>,
+    sizes: Vec<usize>,
+    root: Body,
+}
+
+impl Tree {
+    // Builds the map from `A)B` lines, each saying that B orbits A. The bodies
+    // must form a single tree: every body orbits at most one other, there is
+    // exactly one root and no body orbits itself, even indirectly.
+    pub fn parse(input: &str) -> Result<Tree, String> {
+        let mut names: Vec<String> = Vec::new();
+        let mut ids: HashMap<String, Body> = HashMap::new();
+        let mut parents: Vec<Option<Body>> = Vec::new();
+
+        let mut intern = |name: &str, names: &mut Vec<String>, parents: &mut Vec<Option<Body>>| {
+            *ids.entry(name.to_string()).or_insert_with(|| {
+                names.push(name.to_string());
+                parents.push(None);
+                names.len() - 1
+            })
+        };
+
+        for (row, line) in input.lines().enumerate() {
+            let at = |offset: usize, msg: String| error_at(row, line, offset, msg);
+            let indent = line.len() - line.trim_start().len();
+            let line = line.trim();
+            if line.is_empty() {
+                continue;
+            }
+
+            let (parent, child) = match line.split(')').collect::<Vec<_>>()[..] {
+                [p, c] if !p.trim().is_empty() && !c.trim().is_empty() => (p, c),
+                _ => return Err(at(indent, format!("expected \"A)B\", got \"{}\"", line))),
+            };
+
+            let p = intern(parent.trim(), &mut names, &mut parents);
+            let c = intern(child.trim(), &mut names, &mut parents);
+            if let Some(previous) = parents[c] {
+                if previous != p {
+                    let offset = indent + parent.len() + 1 + child.len() - child.trim_start().len();
+                    let msg = format!("{} already orbits {}", child.trim(), names[previous]);
+                    return Err(at(offset, msg));
+                }
+            }
+            parents[c] = Some(p);
+        }
+
+        if names.is_empty() {
+            return Err(String::from("empty orbit map"));
+        }
+
+        let mut children = vec![Vec::new(); names.len()];
+        for (c, p) in parents.iter().enumerate() {
+            if let Some(p) = *p {
+                children[p].push(c);
+            }
+        }
+
+        let roots: Vec<Body> = (0..names.len()).filter(|&b| parents[b].is_none()).collect();
+
+        // Breadth first from the roots, so every body comes after its parent.
+        // Anything not reached sits on, or hangs off, a cycle.
+        let mut depths = vec![0; names.len()];
+        let mut order = roots.clone();
+        let mut i = 0;
+        while let Some(&b) = order.get(i) {
+            for &c in &children[b] {
+                depths[c] = depths[b] + 1;
+                order.push(c);
+            }
+            i += 1;
+        }
+
+        let mut reached = vec![false; names.len()];
+        for &b in &order {
+            reached[b] = true;
+        }
+
+        if let Some(start) = reached.iter().position(|&r| !r) {
+            let mut seen = HashSet::new();
+            let mut b = start;
+            while seen.insert(b) {
+                b = parents[b].unwrap();
+            }
+
+            let mut cycle = vec![names[b].as_str()];
+            let mut c = parents[b].unwrap();
+            while c != b {
+                cycle.push(&names[c]);
+                c = parents[c].unwrap();
+            }
+            return Err(format!("orbit cycle through {}", cycle.join(", ")));
+        }
+
+        if roots.len() > 1 {
+            let roots: Vec<&str> = roots.iter().map(|&r| names[r].as_str()).collect();
+            return Err(format!("multiple roots: {}", roots.join(", ")));
+        }
+
+        let mut sizes = vec![1; names.len()];
+        for &b in order.iter().rev() {
+            if let Some(p) = parents[b] {
+                sizes[p] += sizes[b];
+            }
+        }
+
+        Ok(Tree {
+            names,
+            ids,
+            parents,
+            children,
+            depths,
+            sizes,
+            root: roots[0],
+        })
+    }
+
+    pub fn len(&self) -> usize {
+        self.names.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.names.is_empty()
+    }
+
+    pub fn root(&self) -> Body {
+        self.root
+    }
+
+    pub fn id(&self, name: &str) -> Option<Body> {
+        self.ids.get(name).copied()
+    }
+
+    pub fn name(&self, body: Body) -> &str {
+        &self.names[body]
+    }
+
+    pub fn bodies(&self) -> impl Iterator<Item = Body> {
+        0..self.names.len()
+    }
+
+    pub fn parent(&self, body: Body) -> Option<Body> {
+        self.parents[body]
+    }
+
+    // The bodies directly orbiting `body`, in input order.
+    pub fn children(&self, body: Body) -> impl Iterator<Item = Body> + '_ {
+        self.children[body].iter().copied()
+    }
+
+    // The number of bodies `body` orbits directly and indirectly.
+    pub fn depth(&self, body: Body) -> usize {
+        self.depths[body]
+    }
+
+    // The number of bodies in the subtree under `body`, itself included.
+    pub fn subtree_size(&self, body: Body) -> usize {
+        self.sizes[body]
+    }
+
+    // The total number of direct and indirect orbits.
+    pub fn checksum(&self) -> usize {
+        self.depths.iter().sum()
+    }
+
+    // Everything `body` orbits, nearest first and ending at the root.
+    pub fn ancestors(&self, body: Body) -> impl Iterator<Item = Body> + '_ {
+        std::iter::successors(self.parents[body], move |&b| self.parents[b])
+    }
+
+    // Everything orbiting `body` directly or indirectly, in depth-first order.
+    pub fn descendants(&self, body: Body) -> Descendants<'_> {
+        Descendants {
+            tree: self,
+            stack: self.children[body].iter().rev().copied().collect(),
+        }
+    }
 }
 
-fn depth(node: &String, tree: &Tree) -> i32 {
-    let mut node = node;
-    let mut depth = 0;
+pub struct Descendants<'a> {
+    tree: &'a Tree,
+    stack: Vec<Body>,
+}
+
+impl Iterator for Descendants<'_> {
+    type Item = Body;
 
-    while let Some(parent) = tree.get(node) {
-        depth += 1;
-        node = parent;
+    fn next(&mut self) -> Option<Body> {
+        let body = self.stack.pop()?;
+        self.stack.extend(self.tree.children[body].iter().rev());
+        Some(body)
     }
+}
 
-    depth
+#[aoc_generator(day6)]
+pub fn input_generator(input: &str) -> Result<Tree, String> {
+    Tree::parse(input)
 }
 
 #[aoc(day6, part1)]
-pub fn solve_part1(input: &Tree) -> i32 {
-    input.keys().map(|child| depth(child, input)).sum()
+pub fn solve_part1(input: &Tree) -> usize {
+    input.checksum()
+}
+
+// Answers lowest common ancestor queries on a tree in logarithmic time by
+// binary lifting: `up[k][b]` is the body 2^k orbits above `b`, or the root.
+pub struct Lca<'a> {
+    tree: &'a Tree,
+    up: Vec<Vec<Body>>,
 }
 
-fn path_to_com(node: &String, tree: &Tree) -> HashSet<String> {
-    let mut node = node;
-    let mut path = HashSet::new();
+impl<'a> Lca<'a> {
+    pub fn new(tree: &'a Tree) -> Lca<'a> {
+        let deepest = tree.depths.iter().copied().max().unwrap_or(0);
+        let levels = (usize::BITS - deepest.leading_zeros()).max(1) as usize;
 
-    while let Some(parent) = tree.get(node) {
-        path.insert(parent.clone());
-        node = parent;
+        let mut up: Vec<Vec<Body>> = Vec::with_capacity(levels);
+        up.push(tree.bodies().map(|b| tree.parent(b).unwrap_or(b)).collect());
+        for k in 1..levels {
+            let level = up[k - 1].iter().map(|&b| up[k - 1][b]).collect();
+            up.push(level);
+        }
+
+        Lca { tree, up }
+    }
+
+    // The body `n` orbits above `body`, stopping at the root.
+    pub fn ancestor(&self, mut body: Body, n: usize) -> Body {
+        for (k, level) in self.up.iter().enumerate() {
+            if n >> k & 1 == 1 {
+                body = level[body];
+            }
+        }
+        if n >> self.up.len() != 0 {
+            body = self.tree.root();
+        }
+        body
     }
 
-    path
+    pub fn lca(&self, a: Body, b: Body) -> Body {
+        let (da, db) = (self.tree.depth(a), self.tree.depth(b));
+        let (mut a, mut b) = if da > db {
+            (self.ancestor(a, da - db), b)
+        } else {
+            (a, self.ancestor(b, db - da))
+        };
+
+        if a == b {
+            return a;
+        }
+
+        for level in self.up.iter().rev() {
+            if level[a] != level[b] {
+                a = level[a];
+                b = level[b];
+            }
+        }
+
+        self.up[0][a]
+    }
+
+    // The number of orbital transfers needed to move from `a` to `b`.
+    pub fn transfers(&self, a: Body, b: Body) -> usize {
+        let common = self.tree.depth(self.lca(a, b));
+        self.tree.depth(a) + self.tree.depth(b) - 2 * common
+    }
+
+    // Every body visited moving from `a` to `b`, both ends included.
+    pub fn path(&self, a: Body, b: Body) -> Vec<Body> {
+        let common = self.lca(a, b);
+        let climb = |from: Body| {
+            std::iter::once(from)
+                .chain(self.tree.ancestors(from))
+                .take_while(move |&x| x != common)
+        };
+
+        let mut path: Vec<Body> = climb(a).collect();
+        path.push(common);
+        let descent: Vec<Body> = climb(b).collect();
+        path.extend(descent.into_iter().rev());
+        path
+    }
+}
+
+// The body that the body called `name` orbits.
+fn orbited_by(tree: &Tree, name: &str) -> Result<Body, String> {
+    let body = tree
+        .id(name)
+        .ok_or_else(|| format!("no body named {}", name))?;
+    tree.parent(body)
+        .ok_or_else(|| format!("{} doesn't orbit anything", name))
 }
 
 #[aoc(day6, part2)]
-pub fn solve_part2(input: &Tree) -> i32 {
-    let your_path = path_to_com(&String::from("YOU"), input);
-    let santas_path = path_to_com(&String::from("SAN"), input);
-    your_path.symmetric_difference(&santas_path).count() as i32
+pub fn solve_part2(input: &Tree) -> Result<usize, String> {
+    let you = orbited_by(input, "YOU")?;
+    let santa = orbited_by(input, "SAN")?;
+    Ok(Lca::new(input).transfers(you, santa))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const EXAMPLE: &str = "COM)B\nB)C\nC)D\nD)E\nE)F\nB)G\nG)H\nD)I\nE)J\nJ)K\nK)L";
+
+    #[test]
+    fn part1() {
+        assert_eq!(solve_part1(&input_generator(EXAMPLE).unwrap()), 42);
+    }
+
+    #[test]
+    fn part2() {
+        let input = format!("{}\nK)YOU\nI)SAN", EXAMPLE);
+        assert_eq!(solve_part2(&input_generator(&input).unwrap()), Ok(4));
+    }
+
+    #[test]
+    fn invalid_maps() {
+        let error = |input| Tree::parse(input).err().unwrap();
+        assert_eq!(error(""), "empty orbit map");
+        assert_eq!(
+            error("COM)B\n  B-C"),
+            "line 2, column 3: expected \"A)B\", got \"B-C\""
+        );
+        assert_eq!(
+            error("COM)"),
+            "line 1, column 1: expected \"A)B\", got \"COM)\""
+        );
+        assert_eq!(
+            error("COM)B\nCOM)C\nB)D\nC)D"),
+            "line 4, column 3: D already orbits B"
+        );
+        assert_eq!(error("COM)B\nX)Y"), "multiple roots: COM, X");
+        assert_eq!(error("COM)B\nC)D\nD)E\nE)C"), "orbit cycle through C, E, D");
+        assert_eq!(error("A)A"), "orbit cycle through A");
+    }
+
+    #[test]
+    fn whitespace() {
+        let tree = Tree::parse(" COM ) B \n\nB)C \n\n").unwrap();
+        assert_eq!(tree.len(), 3);
+        assert_eq!(tree.checksum(), 3);
+        assert_eq!(
+            Tree::parse("COM)B\nX)B").err().unwrap(),
+            "line 2, column 3: B already orbits COM"
+        );
+    }
+
+    #[test]
+    fn iterators() {
+        let tree = input_generator(EXAMPLE).unwrap();
+        let names =
+            |bodies: Vec<Body>| -> Vec<&str> { bodies.into_iter().map(|b| tree.name(b)).collect() };
+        let id = |name| tree.id(name).unwrap();
+
+        assert_eq!(tree.name(tree.root()), "COM");
+        assert_eq!(names(tree.children(id("E")).collect()), ["F", "J"]);
+        assert_eq!(names(tree.ancestors(id("H")).collect()), ["G", "B", "COM"]);
+        assert_eq!(
+            names(tree.descendants(id("D")).collect()),
+            ["E", "F", "J", "K", "L", "I"]
+        );
+        assert_eq!(tree.descendants(tree.root()).count(), tree.len() - 1);
+    }
+
+    #[test]
+    fn depths_and_sizes() {
+        let tree = input_generator(EXAMPLE).unwrap();
+        let id = |name| tree.id(name).unwrap();
+
+        assert_eq!(tree.depth(tree.root()), 0);
+        assert_eq!(tree.depth(id("L")), 7);
+        assert_eq!(tree.subtree_size(tree.root()), tree.len());
+        assert_eq!(tree.subtree_size(id("E")), 5);
+        assert_eq!(tree.subtree_size(id("H")), 1);
+        for b in tree.bodies() {
+            assert_eq!(tree.depth(b), tree.ancestors(b).count());
+            assert_eq!(tree.subtree_size(b), tree.descendants(b).count() + 1);
+        }
+    }
+
+    #[test]
+    fn long_chain() {
+        let n = 200_000;
+        let input: Vec<String> = (1..n).map(|i| format!("{}){}", i - 1, i)).collect();
+        let tree = input_generator(&input.join("\n")).unwrap();
+        assert_eq!(tree.checksum(), n * (n - 1) / 2);
+        assert_eq!(tree.subtree_size(tree.root()), n);
+    }
+
+    #[test]
+    fn routing() {
+        let tree = input_generator(EXAMPLE).unwrap();
+        let lca = Lca::new(&tree);
+        let id = |name| tree.id(name).unwrap();
+        let route = |a, b| -> Vec<&str> {
+            lca.path(id(a), id(b))
+                .into_iter()
+                .map(|b| tree.name(b))
+                .collect()
+        };
+
+        assert_eq!(tree.name(lca.lca(id("L"), id("I"))), "D");
+        assert_eq!(tree.name(lca.lca(id("H"), id("F"))), "B");
+        assert_eq!(tree.name(lca.lca(id("K"), id("E"))), "E");
+        assert_eq!(tree.name(lca.ancestor(id("L"), 3)), "E");
+        assert_eq!(lca.ancestor(id("L"), 100), tree.root());
+
+        assert_eq!(lca.transfers(id("K"), id("I")), 4);
+        assert_eq!(lca.transfers(id("C"), id("C")), 0);
+        assert_eq!(route("K", "I"), ["K", "J", "E", "D", "I"]);
+        assert_eq!(route("D", "L"), ["D", "E", "J", "K", "L"]);
+        assert_eq!(route("H", "COM"), ["H", "G", "B", "COM"]);
+
+        for a in tree.bodies() {
+            for b in tree.bodies() {
+                assert_eq!(lca.path(a, b).len(), lca.transfers(a, b) + 1);
+            }
+        }
+    }
+
+    #[test]
+    fn missing_bodies() {
+        let tree = input_generator(EXAMPLE).unwrap();
+        assert_eq!(solve_part2(&tree), Err(String::from("no body named YOU")));
+        let tree = input_generator("YOU)SAN").unwrap();
+        assert_eq!(
+            solve_part2(&tree),
+            Err(String::from("YOU doesn't orbit anything"))
+        );
+    }
 }
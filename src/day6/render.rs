@@ -0,0 +1,169 @@
+use std::collections::HashSet;
+use std::fmt::Write;
+
+use super::{Body, Lca, Tree};
+
+fn quote(name: &str) -> String {
+    format!("\"{}\"", name.replace('\\', "\\\\").replace('"', "\\\""))
+}
+
+// The bodies called YOU and SAN, when the map has both.
+pub fn santa_route(tree: &Tree) -> Option<(Body, Body)> {
+    Some((tree.id("YOU")?, tree.id("SAN")?))
+}
+
+// Draws the map as a Graphviz digraph with edges pointing from each body to
+// the bodies orbiting it. The ends of `route` are filled in and the path
+// between them is drawn in red.
+pub fn dot(tree: &Tree, route: Option<(Body, Body)>) -> String {
+    let path: HashSet<Body> = match route {
+        Some((a, b)) => Lca::new(tree).path(a, b).into_iter().collect(),
+        None => HashSet::new(),
+    };
+
+    let mut out = String::from("digraph orbits {\n");
+
+    if let Some((a, b)) = route {
+        for &end in &[a, b] {
+            writeln!(
+                out,
+                "  {} [style=filled, fillcolor=red];",
+                quote(tree.name(end))
+            )
+            .unwrap();
+        }
+    }
+
+    for body in tree.bodies() {
+        if let Some(parent) = tree.parent(body) {
+            let style = if path.contains(&body) && path.contains(&parent) {
+                " [color=red, penwidth=2]"
+            } else {
+                ""
+            };
+            writeln!(
+                out,
+                "  {} -> {}{};",
+                quote(tree.name(parent)),
+                quote(tree.name(body)),
+                style
+            )
+            .unwrap();
+        }
+    }
+
+    out.push_str("}\n");
+    out
+}
+
+// Prints the map like the `tree` command. With `collapse`, bodies orbited by
+// exactly one other share a line as `A)B)C`, and chains longer than the
+// given number of bodies only show their ends.
+pub fn tree_view(tree: &Tree, collapse: Option<usize>) -> String {
+    let mut out = String::new();
+    // body, prefix for its children, and its own connector
+    let mut stack = vec![(tree.root(), String::new(), String::new())];
+
+    while let Some((start, prefix, connector)) = stack.pop() {
+        let mut chain = vec![start];
+        if collapse.is_some() {
+            while let [only] = tree.children[*chain.last().unwrap()][..] {
+                chain.push(only);
+            }
+        }
+
+        let names: Vec<&str> = chain.iter().map(|&b| tree.name(b)).collect();
+        let label = match collapse {
+            Some(max) if chain.len() > max.max(2) => format!(
+                "{})…){} ({} bodies)",
+                names[0],
+                names[names.len() - 1],
+                names.len()
+            ),
+            _ => names.join(")"),
+        };
+        writeln!(out, "{}{}", connector, label).unwrap();
+
+        let children = &tree.children[*chain.last().unwrap()];
+        for (i, &child) in children.iter().enumerate().rev() {
+            let last = i + 1 == children.len();
+            let (connector, indent) = if last {
+                ("└── ", "    ")
+            } else {
+                ("├── ", "│   ")
+            };
+            stack.push((
+                child,
+                format!("{}{}", prefix, indent),
+                format!("{}{}", prefix, connector),
+            ));
+        }
+    }
+
+    out
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::day6::input_generator;
+
+    const EXAMPLE: &str = "COM)B\nB)C\nC)D\nD)E\nE)F\nB)G\nG)H\nD)I\nE)J\nJ)K\nK)L\nK)YOU\nI)SAN";
+
+    #[test]
+    fn graphviz() {
+        let tree = input_generator(EXAMPLE).unwrap();
+        let dot = dot(&tree, santa_route(&tree));
+
+        assert!(dot.starts_with("digraph orbits {\n"));
+        assert!(dot.contains("  \"YOU\" [style=filled, fillcolor=red];\n"));
+        assert!(dot.contains("  \"COM\" -> \"B\";\n"));
+        assert!(dot.contains("  \"D\" -> \"I\" [color=red, penwidth=2];\n"));
+        assert_eq!(dot.matches("color=red,").count(), 6);
+        assert_eq!(dot.matches(" -> ").count(), tree.len() - 1);
+        assert!(dot.ends_with("}\n"));
+    }
+
+    #[test]
+    fn text_tree() {
+        let tree = input_generator(EXAMPLE).unwrap();
+        let expected = "\
+COM
+└── B
+    ├── C
+    │   └── D
+    │       ├── E
+    │       │   ├── F
+    │       │   └── J
+    │       │       └── K
+    │       │           ├── L
+    │       │           └── YOU
+    │       └── I
+    │           └── SAN
+    └── G
+        └── H
+";
+        assert_eq!(tree_view(&tree, None), expected);
+
+        let expected = "\
+COM)B
+├── C)D
+│   ├── E
+│   │   ├── F
+│   │   └── J)K
+│   │       ├── L
+│   │       └── YOU
+│   └── I)SAN
+└── G)H
+";
+        assert_eq!(tree_view(&tree, Some(3)), expected);
+
+        let tree = input_generator("COM)A\nA)B\nB)C\nC)D\nA)E").unwrap();
+        let expected = "\
+COM)A
+├── B)…)D (3 bodies)
+└── E
+";
+        assert_eq!(tree_view(&tree, Some(2)), expected);
+    }
+}
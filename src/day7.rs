@@ -1,258 +1,80 @@
-use std::collections::HashMap;
-use std::collections::VecDeque;
-
-use std::convert::TryFrom;
+use crate::intcode::{self, Intcode, Queues};
 
 #[aoc_generator(day7)]
-pub fn input_generator(input: &str) -> Vec<i32> {
-    input.split(',').map(|s| s.parse().unwrap()).collect()
-}
-
-#[derive(Clone, Copy)]
-pub enum Mode {
-    Position,
-    Immediate,
+pub fn input_generator(input: &str) -> Result<Vec<i64>, String> {
+    intcode::parse_program(input)
 }
 
-impl TryFrom<i32> for Mode {
-    type Error = String;
-
-    fn try_from(i: i32) -> Result<Mode, String> {
-        match i {
-            0 => Ok(Mode::Position),
-            1 => Ok(Mode::Immediate),
-            _ => Err(format!("unknown mode: {}", i)),
-        }
-    }
-}
-
-#[derive(Clone, Copy)]
-pub enum Instruction {
-    One(Mode, Mode),
-    Two(Mode, Mode),
-    Three,
-    Four(Mode),
-    Five(Mode, Mode),
-    Six(Mode, Mode),
-    Seven(Mode, Mode),
-    Eight(Mode, Mode),
-    Halt,
-}
+type Phases = (i64, i64, i64, i64, i64);
 
-impl TryFrom<i32> for Instruction {
-    type Error = String;
-
-    fn try_from(i: i32) -> Result<Instruction, String> {
-        let a = (i / 10000) % 10;
-        let b = (i / 1000) % 10;
-        let c = (i / 100) % 10;
-        let d = (i / 10) % 10;
-        let e = i % 10;
-
-        match (a, b, c, d, e) {
-            (0, 0, 0, 9, 9) => Ok(Instruction::Halt),
-            (_, m2, m1, 0, 1) => Ok(Instruction::One(
-                Mode::try_from(m1).unwrap(),
-                Mode::try_from(m2).unwrap(),
-            )),
-            (_, m2, m1, 0, 2) => Ok(Instruction::Two(
-                Mode::try_from(m1).unwrap(),
-                Mode::try_from(m2).unwrap(),
-            )),
-            (_, _, _, 0, 3) => Ok(Instruction::Three),
-            (_, _, m1, 0, 4) => Ok(Instruction::Four(Mode::try_from(m1).unwrap())),
-            (_, m2, m1, 0, 5) => Ok(Instruction::Five(
-                Mode::try_from(m1).unwrap(),
-                Mode::try_from(m2).unwrap(),
-            )),
-            (_, m2, m1, 0, 6) => Ok(Instruction::Six(
-                Mode::try_from(m1).unwrap(),
-                Mode::try_from(m2).unwrap(),
-            )),
-            (_, m2, m1, 0, 7) => Ok(Instruction::Seven(
-                Mode::try_from(m1).unwrap(),
-                Mode::try_from(m2).unwrap(),
-            )),
-            (_, m2, m1, 0, 8) => Ok(Instruction::Eight(
-                Mode::try_from(m1).unwrap(),
-                Mode::try_from(m2).unwrap(),
-            )),
-            _ => Err(format!("unknown instruction {}", i)),
-        }
-    }
+fn amp_error(amp: usize, msg: &str) -> String {
+    format!("amplifier {} {}", (b'A' + amp as u8) as char, msg)
 }
 
-struct Intcode {
-    memory: HashMap<usize, i32>,
-    pc: usize,
-    halted: bool,
+fn no_signal(amp: usize) -> String {
+    amp_error(amp, "produced no signal")
 }
 
-impl Intcode {
-    fn new(memory: &Vec<i32>) -> Intcode {
-        Intcode {
-            memory: memory.iter().enumerate().map(|(k, v)| (k, *v)).collect(),
-            pc: 0,
-            halted: false,
-        }
-    }
-
-    fn run(&mut self, bus: &mut VecDeque<i32>) {
-        loop {
-            match Instruction::try_from(*self.memory.get(&self.pc).unwrap()).unwrap() {
-                Instruction::Halt => {
-                    self.halted = true;
-                    break;
-                }
-                Instruction::One(m1, m2) => {
-                    let p1 = self.value(m1, *self.memory.get(&(self.pc + 1)).unwrap());
-                    let p2 = self.value(m2, *self.memory.get(&(self.pc + 2)).unwrap());
-                    let p3 = *self.memory.get(&(self.pc + 3)).unwrap();
-                    self.memory.insert(p3 as usize, p1 + p2);
-                    self.pc += 4;
-                }
-                Instruction::Two(m1, m2) => {
-                    let p1 = self.value(m1, *self.memory.get(&(self.pc + 1)).unwrap());
-                    let p2 = self.value(m2, *self.memory.get(&(self.pc + 2)).unwrap());
-                    let p3 = *self.memory.get(&(self.pc + 3)).unwrap();
-                    self.memory.insert(p3 as usize, p1 * p2);
-                    self.pc += 4;
-                }
-                Instruction::Three => {
-                    let p1 = *self.memory.get(&(self.pc + 1)).unwrap();
-                    self.memory.insert(p1 as usize, bus.pop_front().unwrap());
-                    self.pc += 2;
-                }
-                Instruction::Four(m) => {
-                    let p1 = self.value(m, *self.memory.get(&(self.pc + 1)).unwrap());
-                    bus.push_back(p1);
-                    self.pc += 2;
-                    break; // hack: yield
-                }
-                Instruction::Five(m1, m2) => {
-                    let p1 = self.value(m1, *self.memory.get(&(self.pc + 1)).unwrap());
-                    let p2 = self.value(m2, *self.memory.get(&(self.pc + 2)).unwrap());
-
-                    if p1 != 0 {
-                        self.pc = p2 as usize;
-                    } else {
-                        self.pc += 3;
-                    }
-                }
-                Instruction::Six(m1, m2) => {
-                    let p1 = self.value(m1, *self.memory.get(&(self.pc + 1)).unwrap());
-                    let p2 = self.value(m2, *self.memory.get(&(self.pc + 2)).unwrap());
-
-                    if p1 == 0 {
-                        self.pc = p2 as usize;
-                    } else {
-                        self.pc += 3;
-                    }
-                }
-                Instruction::Seven(m1, m2) => {
-                    let p1 = self.value(m1, *self.memory.get(&(self.pc + 1)).unwrap());
-                    let p2 = self.value(m2, *self.memory.get(&(self.pc + 2)).unwrap());
-                    let p3 = *self.memory.get(&(self.pc + 3)).unwrap();
-
-                    if p1 < p2 {
-                        self.memory.insert(p3 as usize, 1);
-                    } else {
-                        self.memory.insert(p3 as usize, 0);
-                    }
-
-                    self.pc += 4;
-                }
-                Instruction::Eight(m1, m2) => {
-                    let p1 = self.value(m1, *self.memory.get(&(self.pc + 1)).unwrap());
-                    let p2 = self.value(m2, *self.memory.get(&(self.pc + 2)).unwrap());
-                    let p3 = *self.memory.get(&(self.pc + 3)).unwrap();
-
-                    if p1 == p2 {
-                        self.memory.insert(p3 as usize, 1);
-                    } else {
-                        self.memory.insert(p3 as usize, 0);
-                    }
-
-                    self.pc += 4;
-                }
-            }
+fn thrust_level(program: &[i64], phases: Phases) -> Result<i64, String> {
+    let (a, b, c, d, e) = phases;
+    let mut signal = 0;
+
+    for (amp, &phase) in [a, b, c, d, e].iter().enumerate() {
+        let mut io = Queues::new(vec![phase, signal]);
+        let mut computer = Intcode::new(program);
+        computer.run(&mut io);
+        if let Some(e) = computer.error {
+            return Err(amp_error(amp, &format!("failed: {}", e)));
         }
+        signal = io.output.pop_front().ok_or_else(|| no_signal(amp))?;
     }
 
-    fn value(&self, mode: Mode, value: i32) -> i32 {
-        match mode {
-            Mode::Position => *self.memory.get(&(value as usize)).unwrap(),
-            Mode::Immediate => value,
-        }
-    }
+    Ok(signal)
 }
 
-fn thrust_level(program: &Vec<i32>, phases: (i32, i32, i32, i32, i32)) -> i32 {
+fn feedback_loop(program: &[i64], phases: Phases) -> Result<i64, String> {
     let (a, b, c, d, e) = phases;
-    let mut bus = VecDeque::new();
-    let mut amp_a = Intcode::new(program);
-    let mut amp_b = Intcode::new(program);
-    let mut amp_c = Intcode::new(program);
-    let mut amp_d = Intcode::new(program);
-    let mut amp_e = Intcode::new(program);
-
-    bus.push_back(a);
-    bus.push_back(0);
-    amp_a.run(&mut bus);
-
-    bus.push_front(b);
-    amp_b.run(&mut bus);
-
-    bus.push_front(c);
-    amp_c.run(&mut bus);
+    let mut amps: Vec<Intcode> = (0..5).map(|_| Intcode::new(program)).collect();
+    let mut buses: Vec<Queues> = [a, b, c, d, e]
+        .iter()
+        .map(|&phase| Queues::new(vec![phase]))
+        .collect();
+    let mut signal = None;
+
+    buses[0].input.push_back(0);
+
+    while !amps[4].halted {
+        // a pass where no amplifier halts, reads or writes will repeat forever
+        let mut progress = false;
+
+        for (i, amp) in amps.iter_mut().enumerate() {
+            let (halted, waiting) = (amp.halted, buses[i].input.len());
+            amp.run(&mut buses[i]);
+            if let Some(e) = &amp.error {
+                return Err(amp_error(i, &format!("failed: {}", e)));
+            }
+            let output: Vec<i64> = buses[i].output.drain(..).collect();
 
-    bus.push_front(d);
-    amp_d.run(&mut bus);
+            progress |=
+                amp.halted != halted || buses[i].input.len() < waiting || !output.is_empty();
 
-    bus.push_front(e);
-    amp_e.run(&mut bus);
+            if i == 4 {
+                signal = output.last().copied().or(signal);
+            }
 
-    bus.pop_front().unwrap()
-}
+            buses[(i + 1) % 5].input.extend(output);
+        }
 
-fn feedback_loop(program: &Vec<i32>, phases: (i32, i32, i32, i32, i32)) -> i32 {
-    let (a, b, c, d, e) = phases;
-    let mut bus = VecDeque::new();
-    let mut amp_a = Intcode::new(program);
-    let mut amp_b = Intcode::new(program);
-    let mut amp_c = Intcode::new(program);
-    let mut amp_d = Intcode::new(program);
-    let mut amp_e = Intcode::new(program);
-
-    bus.push_front(a);
-    bus.push_back(0);
-    amp_a.run(&mut bus);
-
-    bus.push_front(b);
-    amp_b.run(&mut bus);
-
-    bus.push_front(c);
-    amp_c.run(&mut bus);
-
-    bus.push_front(d);
-    amp_d.run(&mut bus);
-
-    bus.push_front(e);
-    amp_e.run(&mut bus);
-
-    while !amp_a.halted && !amp_b.halted && !amp_c.halted && !amp_d.halted && !amp_e.halted {
-        amp_a.run(&mut bus);
-        amp_b.run(&mut bus);
-        amp_c.run(&mut bus);
-        amp_d.run(&mut bus);
-        amp_e.run(&mut bus);
+        if !progress {
+            return Err(String::from("amplifiers deadlocked"));
+        }
     }
 
-    bus.pop_front().unwrap()
+    signal.ok_or_else(|| no_signal(4))
 }
 
 #[aoc(day7, part1)]
-pub fn solve_part1(input: &Vec<i32>) -> Option<i32> {
+pub fn solve_part1(input: &[i64]) -> Result<i64, String> {
     let phases = (0..=44444)
         .map(|i| {
             (
@@ -276,11 +98,15 @@ pub fn solve_part1(input: &Vec<i32>) -> Option<i32> {
                 && (d != e)
         });
 
-    phases.map(|phase| thrust_level(input, phase)).max()
+    let mut best = i64::MIN;
+    for phase in phases {
+        best = best.max(thrust_level(input, phase)?);
+    }
+    Ok(best)
 }
 
 #[aoc(day7, part2)]
-pub fn solve_part2(input: &Vec<i32>) -> Option<i32> {
+pub fn solve_part2(input: &[i64]) -> Result<i64, String> {
     let phases = (55555..=99999)
         .map(|i| {
             (
@@ -304,7 +130,11 @@ pub fn solve_part2(input: &Vec<i32>) -> Option<i32> {
                 && (d != e)
         });
 
-    phases.map(|phase| feedback_loop(input, phase)).max()
+    let mut best = i64::MIN;
+    for phase in phases {
+        best = best.max(feedback_loop(input, phase)?);
+    }
+    Ok(best)
 }
 
 #[cfg(test)]
@@ -314,57 +144,95 @@ mod tests {
     #[test]
     fn part1_a() {
         let input = "3,15,3,16,1002,16,10,16,1,16,15,15,4,15,99,0,0";
-        let input = input_generator(input);
-        assert_eq!(solve_part1(&input), Some(43210));
+        let input = input_generator(input).unwrap();
+        assert_eq!(solve_part1(&input), Ok(43210));
     }
 
     #[test]
     fn part1_b() {
         let input = "3,23,3,24,1002,24,10,24,1002,23,-1,23,101,5,23,23,1,24,23,23,4,23,99,0,0";
-        let input = input_generator(input);
-        assert_eq!(solve_part1(&input), Some(54321));
+        let input = input_generator(input).unwrap();
+        assert_eq!(solve_part1(&input), Ok(54321));
     }
 
     #[test]
     fn part1_c() {
         let input = "3,31,3,32,1002,32,10,32,1001,31,-2,31,1007,31,0,33,1002,33,7,33,1,33,31,31,1,32,31,31,4,31,99,0,0,0";
-        let input = input_generator(input);
-        assert_eq!(solve_part1(&input), Some(65210));
+        let input = input_generator(input).unwrap();
+        assert_eq!(solve_part1(&input), Ok(65210));
     }
 
     #[test]
     fn thrust_level_calculation_a() {
         let input = "3,15,3,16,1002,16,10,16,1,16,15,15,4,15,99,0,0";
-        let input = input_generator(input);
-        assert_eq!(thrust_level(&input, (4, 3, 2, 1, 0)), 43210);
+        let input = input_generator(input).unwrap();
+        assert_eq!(thrust_level(&input, (4, 3, 2, 1, 0)), Ok(43210));
     }
 
     #[test]
     fn thrust_level_calculation_b() {
         let input = "3,23,3,24,1002,24,10,24,1002,23,-1,23,101,5,23,23,1,24,23,23,4,23,99,0,0";
-        let input = input_generator(input);
-        assert_eq!(thrust_level(&input, (0, 1, 2, 3, 4)), 54321);
+        let input = input_generator(input).unwrap();
+        assert_eq!(thrust_level(&input, (0, 1, 2, 3, 4)), Ok(54321));
     }
 
     #[test]
     fn thrust_level_calculation_c() {
         let input = "3,31,3,32,1002,32,10,32,1001,31,-2,31,1007,31,0,33,1002,33,7,33,1,33,31,31,1,32,31,31,4,31,99,0,0,0";
-        let input = input_generator(input);
-        assert_eq!(thrust_level(&input, (1, 0, 4, 3, 2)), 65210);
+        let input = input_generator(input).unwrap();
+        assert_eq!(thrust_level(&input, (1, 0, 4, 3, 2)), Ok(65210));
     }
 
     #[test]
     fn feedback_loop_a() {
         let input =
             "3,26,1001,26,-4,26,3,27,1002,27,2,27,1,27,26,27,4,27,1001,28,-1,28,1005,28,6,99,0,0,5";
-        let input = input_generator(input);
-        assert_eq!(feedback_loop(&input, (9, 8, 7, 6, 5)), 139629729);
+        let input = input_generator(input).unwrap();
+        assert_eq!(feedback_loop(&input, (9, 8, 7, 6, 5)), Ok(139629729));
     }
 
     #[test]
     fn feedback_loop_b() {
         let input = "3,52,1001,52,-5,52,3,53,1,52,56,54,1007,54,5,55,1005,55,26,1001,54,-5,54,1105,1,12,1,53,54,53,1008,54,0,55,1001,55,1,55,2,53,55,53,4,53,1001,56,-1,56,1005,56,6,99,0,0,0,0,10";
-        let input = input_generator(input);
-        assert_eq!(feedback_loop(&input, (9, 7, 8, 5, 6)), 18216);
+        let input = input_generator(input).unwrap();
+        assert_eq!(feedback_loop(&input, (9, 7, 8, 5, 6)), Ok(18216));
+    }
+
+    #[test]
+    fn no_signal() {
+        let input = input_generator("99").unwrap();
+        assert_eq!(
+            thrust_level(&input, (0, 1, 2, 3, 4)),
+            Err(String::from("amplifier A produced no signal"))
+        );
+        assert!(solve_part1(&input).is_err());
+        assert_eq!(
+            solve_part2(&input),
+            Err(String::from("amplifier E produced no signal"))
+        );
+
+        let input = input_generator("3,0,3,0,99").unwrap();
+        assert_eq!(
+            feedback_loop(&input, (5, 6, 7, 8, 9)),
+            Err(String::from("amplifiers deadlocked"))
+        );
+
+        let input = input_generator("3,0,3,0,42").unwrap();
+        assert_eq!(
+            thrust_level(&input, (0, 1, 2, 3, 4)),
+            Err(String::from(
+                "amplifier A failed: unknown instruction 42 at pc 4"
+            ))
+        );
+        assert!(solve_part2(&input).is_err());
+    }
+
+    #[test]
+    fn malformed_input() {
+        assert_eq!(input_generator("3,0,99\r\n"), Ok(vec![3, 0, 99]));
+        assert_eq!(
+            input_generator("3,0,99,"),
+            Err(String::from("line 1, column 8: missing value \"\""))
+        );
     }
 }
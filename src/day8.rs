@@ -1,40 +1,345 @@
-use std::char;
-use std::iter;
+use std::fmt;
 use std::slice;
 
-#[aoc_generator(day8)]
-pub fn input_generator(input: &str) -> Vec<u32> {
-    input.chars().map(|c| c.to_digit(10).unwrap()).collect()
+use crate::input::error_at;
+use crate::xorshift::XorShift;
+
+pub mod export;
+pub mod ocr;
+
+pub const WIDTH: usize = 25;
+pub const HEIGHT: usize = 6;
+
+pub const BLACK: u32 = 0;
+pub const WHITE: u32 = 1;
+pub const TRANSPARENT: u32 = 2;
+
+// one per decimal digit
+pub const COLORS: usize = 10;
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct SpaceImage {
+    pub width: usize,
+    pub height: usize,
+    pixels: Vec<u32>,
 }
 
-#[aoc(day8, part1)]
-pub fn solve_part1(input: &[u32]) -> i32 {
-    let layer_with_least_zeroes = input
-        .chunks(25 * 6)
-        .min_by_key(|layer| layer.iter().filter(|px| **px == 0).count())
-        .unwrap();
+impl SpaceImage {
+    // Splits a stream of digits, which may run over several lines, into
+    // layers of `width` x `height` pixels.
+    pub fn parse(input: &str, width: usize, height: usize) -> Result<SpaceImage, String> {
+        let mut pixels = Vec::new();
+        for (row, line) in input.lines().enumerate() {
+            for (offset, c) in line.trim_end().char_indices() {
+                let pixel = c
+                    .to_digit(10)
+                    .ok_or_else(|| error_at(row, line, offset, format!("invalid pixel {:?}", c)))?;
+                pixels.push(pixel);
+            }
+        }
+
+        SpaceImage::new(pixels, width, height)
+    }
+
+    pub fn new(pixels: Vec<u32>, width: usize, height: usize) -> Result<SpaceImage, String> {
+        let size = width * height;
+        if size == 0 {
+            return Err(format!("invalid image size {}x{}", width, height));
+        }
+        if pixels.is_empty() || !pixels.len().is_multiple_of(size) {
+            return Err(format!(
+                "{} pixels don't make whole {}x{} layers",
+                pixels.len(),
+                width,
+                height
+            ));
+        }
+
+        if let Some(i) = pixels.iter().position(|&c| c as usize >= COLORS) {
+            return Err(format!("pixel {} has invalid colour {}", i, pixels[i]));
+        }
+
+        Ok(SpaceImage {
+            width,
+            height,
+            pixels,
+        })
+    }
+
+    // Builds `layers` layers that composite to `target`. Each pixel shows
+    // through on a randomly chosen layer: the layers above it are transparent
+    // there and the ones below get random colours, transparent included.
+    pub fn encode(
+        target: &[u32],
+        width: usize,
+        layers: usize,
+        seed: u64,
+    ) -> Result<SpaceImage, String> {
+        if layers == 0 {
+            return Err(String::from("an image needs at least one layer"));
+        }
+        if width == 0 || !target.len().is_multiple_of(width) {
+            return Err(format!(
+                "{} pixels don't make rows of {}",
+                target.len(),
+                width
+            ));
+        }
+        if let Some(i) = target
+            .iter()
+            .position(|&c| c == TRANSPARENT || c as usize >= COLORS)
+        {
+            return Err(format!("pixel {} can't be shown as {}", i, target[i]));
+        }
+
+        let mut rng = XorShift::new(seed);
+        let colors = target
+            .iter()
+            .copied()
+            .max()
+            .unwrap_or(WHITE)
+            .max(TRANSPARENT)
+            + 1;
+
+        let size = target.len();
+        let mut pixels = vec![TRANSPARENT; size * layers];
+        for (i, &c) in target.iter().enumerate() {
+            let shown = rng.below(layers);
+            pixels[shown * size + i] = c;
+            for layer in shown + 1..layers {
+                pixels[layer * size + i] = rng.below(colors as usize) as u32;
+            }
+        }
+
+        SpaceImage::new(pixels, width, size / width)
+    }
+
+    pub fn histogram(&self, layer: usize) -> Histogram {
+        Histogram::of(self.layer(layer))
+    }
+
+    pub fn histograms(&self) -> impl Iterator<Item = Histogram> + '_ {
+        self.layers().map(Histogram::of)
+    }
+
+    // The first layer minimizing `key`, along with its histogram.
+    pub fn select_layer<K, F>(&self, mut key: F) -> (usize, Histogram)
+    where
+        K: Ord,
+        F: FnMut(&Histogram) -> K,
+    {
+        self.histograms()
+            .enumerate()
+            .min_by_key(|(_, h)| key(h))
+            .unwrap()
+    }
+
+    pub fn layer_size(&self) -> usize {
+        self.width * self.height
+    }
+
+    pub fn layer_count(&self) -> usize {
+        self.pixels.len() / self.layer_size()
+    }
+
+    // The pixels of one layer, row by row from the top left.
+    pub fn layer(&self, layer: usize) -> &[u32] {
+        let size = self.layer_size();
+        &self.pixels[layer * size..(layer + 1) * size]
+    }
+
+    pub fn layers(&self) -> slice::Chunks<'_, u32> {
+        self.pixels.chunks(self.layer_size())
+    }
+
+    pub fn pixel(&self, x: usize, y: usize, layer: usize) -> u32 {
+        assert!(x < self.width && y < self.height, "pixel out of bounds");
+        self.layer(layer)[y * self.width + x]
+    }
+
+    // The colour of each pixel seen through all the layers, or transparent
+    // where every layer is.
+    pub fn decode(&self) -> Vec<u32> {
+        (0..self.layer_size())
+            .map(|i| {
+                self.layers()
+                    .map(|layer| layer[i])
+                    .find(|&c| c != TRANSPARENT)
+                    .unwrap_or(TRANSPARENT)
+            })
+            .collect()
+    }
+
+    // Draws the decoded image row by row, white as full blocks and
+    // transparent as light shade.
+    pub fn render(&self) -> String {
+        let mut out = String::with_capacity((self.width + 1) * self.height * 3);
+        for row in self.decode().chunks(self.width) {
+            out.extend(row.iter().map(|&c| match c {
+                WHITE => '█',
+                TRANSPARENT => '░',
+                _ => ' ',
+            }));
+            out.push('\n');
+        }
+        out
+    }
+
+    // The letters spelled out by the decoded image.
+    pub fn text(&self) -> Result<String, String> {
+        ocr::recognize(&self.decode(), self.width)
+    }
+}
+
+// How many pixels of a layer have each of the ten colours.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct Histogram {
+    counts: [usize; COLORS],
+}
+
+impl Histogram {
+    // Only called on layers of a `SpaceImage`, whose colours are checked
+    // when it is built.
+    fn of(pixels: &[u32]) -> Histogram {
+        let mut histogram = Histogram::default();
+        for &c in pixels {
+            histogram.counts[c as usize] += 1;
+        }
+        histogram
+    }
+
+    pub fn count(&self, color: u32) -> usize {
+        self.counts.get(color as usize).copied().unwrap_or(0)
+    }
+
+    // The colours present, with their counts, in ascending order of colour.
+    pub fn colors(&self) -> impl Iterator<Item = (u32, usize)> + '_ {
+        (0..COLORS as u32)
+            .map(move |c| (c, self.count(c)))
+            .filter(|&(_, n)| n > 0)
+    }
+}
 
-    let (ones, twos) = layer_with_least_zeroes
-        .iter()
-        .fold((0, 0), |(x, y), px| match px {
-            1 => (x + 1, y),
-            2 => (x, y + 1),
-            _ => (x, y),
-        });
+// The image as the digit stream it was parsed from.
+impl fmt::Display for SpaceImage {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        for &c in &self.pixels {
+            write!(f, "{}", c)?;
+        }
+        Ok(())
+    }
+}
 
-    ones * twos
+#[aoc_generator(day8)]
+pub fn input_generator(input: &str) -> Result<SpaceImage, String> {
+    SpaceImage::parse(input, WIDTH, HEIGHT)
+}
+
+#[aoc(day8, part1)]
+pub fn solve_part1(input: &SpaceImage) -> usize {
+    let (_, histogram) = input.select_layer(|h| h.count(BLACK));
+    histogram.count(WHITE) * histogram.count(TRANSPARENT)
 }
 
 #[aoc(day8, part2)]
-pub fn solve_part2(input: &[u32]) -> String {
-    let color = |pixel: iter::StepBy<iter::Skip<slice::Iter<u32>>>| {
-        pixel
-            .skip_while(|l| **l == 2)
-            .next()
-            .map(|c| char::from_digit(*c, 10).unwrap())
-            .unwrap()
-    };
+pub fn solve_part2(input: &SpaceImage) -> Result<String, String> {
+    input
+        .text()
+        .map_err(|e| format!("{}\n{}", e, input.render()))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn layers() {
+        let image = SpaceImage::parse("123456789012", 3, 2).unwrap();
+        assert_eq!(image.layer_count(), 2);
+        assert_eq!(image.layer(1), &[7, 8, 9, 0, 1, 2]);
+        assert_eq!(image.pixel(2, 0, 0), 3);
+        assert_eq!(image.pixel(0, 1, 1), 0);
+    }
+
+    #[test]
+    fn decode() {
+        let image = SpaceImage::parse("0222112222120000", 2, 2).unwrap();
+        assert_eq!(image.decode(), vec![0, 1, 1, 0]);
+    }
+
+    #[test]
+    fn invalid_images() {
+        let error = |input, width, height| SpaceImage::parse(input, width, height).err().unwrap();
+        assert_eq!(
+            error("1234567", 3, 2),
+            "7 pixels don't make whole 3x2 layers"
+        );
+        assert_eq!(error("", 3, 2), "0 pixels don't make whole 3x2 layers");
+        assert_eq!(error("123", 0, 2), "invalid image size 0x2");
+        assert_eq!(error("12a456", 3, 2), "line 1, column 3: invalid pixel 'a'");
+        assert_eq!(
+            error("123\n4 56", 3, 2),
+            "line 2, column 2: invalid pixel ' '"
+        );
+        assert_eq!(
+            SpaceImage::parse("123\n456 \n\n", 3, 2)
+                .unwrap()
+                .layer_count(),
+            1
+        );
+    }
+
+    #[test]
+    fn render() {
+        let image = SpaceImage::parse("0222112222120002", 2, 2).unwrap();
+        assert_eq!(image.render(), " █\n█░\n");
+    }
+
+    #[test]
+    fn encode() {
+        let target: Vec<u32> = (0..WIDTH * HEIGHT).map(|i| (i % 7 % 2) as u32).collect();
+
+        for &layers in &[1, 2, 10, 100] {
+            let image = SpaceImage::encode(&target, WIDTH, layers, layers as u64).unwrap();
+            assert_eq!(image.layer_count(), layers);
+            assert_eq!(image.decode(), target);
+
+            let input = image.to_string();
+            assert_eq!(input.len(), WIDTH * HEIGHT * layers);
+            assert_eq!(input_generator(&input).unwrap(), image);
+        }
+
+        let image = SpaceImage::encode(&target, WIDTH, 100, 7).unwrap();
+        assert!(image.layer(0).contains(&TRANSPARENT));
+
+        let colors = [3, 0, 5, 1];
+        let image = SpaceImage::encode(&colors, 2, 8, 1).unwrap();
+        assert_eq!(image.decode(), colors);
+
+        assert!(SpaceImage::encode(&target, WIDTH, 0, 1).is_err());
+        assert!(SpaceImage::encode(&[0, 2], 2, 3, 1).is_err());
+        assert!(SpaceImage::encode(&[0, 1, 0], 2, 3, 1).is_err());
+    }
+
+    #[test]
+    fn histograms() {
+        let image = SpaceImage::parse("123456789012", 3, 2).unwrap();
+        assert_eq!(
+            image.histogram(0).colors().collect::<Vec<_>>(),
+            [(1, 1), (2, 1), (3, 1), (4, 1), (5, 1), (6, 1)]
+        );
+        assert_eq!(image.histogram(1).count(9), 1);
+        assert_eq!(image.histogram(1).count(12), 0);
+        assert_eq!(solve_part1(&image), 1);
+
+        // the layer with the most eights and fewest nines
+        let (layer, _) = image.select_layer(|h| (std::cmp::Reverse(h.count(8)), h.count(9)));
+        assert_eq!(layer, 1);
 
-    let pixel = |n| input.iter().skip(n).step_by(25 * 6);
-    (0..(25 * 6)).map(|px| color(pixel(px))).collect()
+        let image = SpaceImage::parse("000111222333444111", 3, 1).unwrap();
+        let (layer, histogram) = image.select_layer(|h| h.count(BLACK) + h.count(3));
+        assert_eq!((layer, histogram.count(1)), (1, 3));
+        assert_eq!(image.histograms().map(|h| h.count(4)).sum::<usize>(), 3);
+        assert!(SpaceImage::new(vec![0, 10], 2, 1).is_err());
+    }
 }
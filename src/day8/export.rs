@@ -0,0 +1,268 @@
+use std::fmt;
+use std::fs;
+use std::io;
+use std::path::{Path, PathBuf};
+use std::str::FromStr;
+
+use super::{SpaceImage, BLACK, TRANSPARENT, WHITE};
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Format {
+    Pgm,
+    Ppm,
+    Png,
+}
+
+impl Format {
+    pub fn extension(self) -> &'static str {
+        match self {
+            Format::Pgm => "pgm",
+            Format::Ppm => "ppm",
+            Format::Png => "png",
+        }
+    }
+}
+
+impl fmt::Display for Format {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.write_str(self.extension())
+    }
+}
+
+impl FromStr for Format {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.to_ascii_lowercase().as_str() {
+            "pgm" => Ok(Format::Pgm),
+            "ppm" => Ok(Format::Ppm),
+            "png" => Ok(Format::Png),
+            _ => Err(format!("unknown image format \"{}\"", s)),
+        }
+    }
+}
+
+// Colours past white and transparent, for images that use more digits.
+const PALETTE: [[u8; 3]; 7] = [
+    [214, 39, 40],
+    [44, 160, 44],
+    [31, 119, 180],
+    [255, 127, 14],
+    [148, 103, 189],
+    [23, 190, 207],
+    [188, 189, 34],
+];
+
+fn rgb(c: u32) -> [u8; 3] {
+    match c {
+        BLACK => [0, 0, 0],
+        WHITE => [255, 255, 255],
+        TRANSPARENT => [128, 128, 128],
+        _ => PALETTE[(c as usize - 3) % PALETTE.len()],
+    }
+}
+
+fn gray(c: u32) -> u8 {
+    let [r, g, b] = rgb(c);
+    ((299 * r as u32 + 587 * g as u32 + 114 * b as u32) / 1000) as u8
+}
+
+const fn crc_table() -> [u32; 256] {
+    let mut table = [0; 256];
+    let mut n = 0;
+    while n < 256 {
+        let mut c = n as u32;
+        let mut k = 0;
+        while k < 8 {
+            c = if c & 1 == 1 {
+                0xedb8_8320 ^ (c >> 1)
+            } else {
+                c >> 1
+            };
+            k += 1;
+        }
+        table[n] = c;
+        n += 1;
+    }
+    table
+}
+
+const CRC_TABLE: [u32; 256] = crc_table();
+
+fn crc32(bytes: &[u8]) -> u32 {
+    !bytes.iter().fold(!0, |crc, &b| {
+        CRC_TABLE[((crc ^ b as u32) & 0xff) as usize] ^ (crc >> 8)
+    })
+}
+
+fn adler32(bytes: &[u8]) -> u32 {
+    let (a, b) = bytes.iter().fold((1, 0), |(a, b), &x| {
+        let a = (a + x as u32) % 65521;
+        (a, (b + a) % 65521)
+    });
+    b << 16 | a
+}
+
+fn chunk(out: &mut Vec<u8>, kind: &[u8; 4], data: &[u8]) {
+    out.extend(&(data.len() as u32).to_be_bytes());
+    let start = out.len();
+    out.extend(kind);
+    out.extend(data);
+    let crc = crc32(&out[start..]);
+    out.extend(&crc.to_be_bytes());
+}
+
+// An RGB PNG whose pixel data is stored without compression, which keeps it
+// free of dependencies. The images are tiny anyway.
+fn png(width: usize, height: usize, rgb: &[u8]) -> Vec<u8> {
+    let mut raw = Vec::with_capacity(height * (width * 3 + 1));
+    for row in rgb.chunks(width * 3) {
+        raw.push(0);
+        raw.extend(row);
+    }
+
+    let mut zlib = vec![0x78, 0x01];
+    let blocks: Vec<&[u8]> = raw.chunks(0xffff).collect();
+    for (i, block) in blocks.iter().enumerate() {
+        zlib.push((i + 1 == blocks.len()) as u8);
+        let len = block.len() as u16;
+        zlib.extend(&len.to_le_bytes());
+        zlib.extend(&(!len).to_le_bytes());
+        zlib.extend(*block);
+    }
+    zlib.extend(&adler32(&raw).to_be_bytes());
+
+    let mut header = Vec::with_capacity(13);
+    header.extend(&(width as u32).to_be_bytes());
+    header.extend(&(height as u32).to_be_bytes());
+    header.extend(&[8, 2, 0, 0, 0]);
+
+    let mut out = b"\x89PNG\r\n\x1a\n".to_vec();
+    chunk(&mut out, b"IHDR", &header);
+    chunk(&mut out, b"IDAT", &zlib);
+    chunk(&mut out, b"IEND", &[]);
+    out
+}
+
+// Encodes a grid of colours, blowing every pixel up into a `scale` x `scale`
+// square.
+pub fn encode(pixels: &[u32], width: usize, scale: usize, format: Format) -> Vec<u8> {
+    assert!(scale > 0, "scale must be positive");
+    let height = pixels.len() / width;
+    let (w, h) = (width * scale, height * scale);
+
+    let scaled = pixels
+        .chunks(width)
+        .flat_map(|row| std::iter::repeat_n(row, scale))
+        .flat_map(|row| row.iter().flat_map(|&c| std::iter::repeat_n(c, scale)));
+
+    match format {
+        Format::Pgm => {
+            let mut out = format!("P5\n{} {}\n255\n", w, h).into_bytes();
+            out.extend(scaled.map(gray));
+            out
+        }
+        Format::Ppm => {
+            let mut out = format!("P6\n{} {}\n255\n", w, h).into_bytes();
+            out.extend(scaled.flat_map(rgb));
+            out
+        }
+        Format::Png => png(w, h, &scaled.flat_map(rgb).collect::<Vec<u8>>()),
+    }
+}
+
+impl SpaceImage {
+    pub fn export_layer(&self, layer: usize, scale: usize, format: Format) -> Vec<u8> {
+        encode(self.layer(layer), self.width, scale, format)
+    }
+
+    pub fn export(&self, scale: usize, format: Format) -> Vec<u8> {
+        encode(&self.decode(), self.width, scale, format)
+    }
+
+    // Writes every layer as `layer-N` and the composited image as `image`
+    // into `dir`, returning the paths written.
+    pub fn save(&self, dir: &Path, scale: usize, format: Format) -> io::Result<Vec<PathBuf>> {
+        fs::create_dir_all(dir)?;
+        let digits = self.layer_count().to_string().len();
+        let mut paths = Vec::with_capacity(self.layer_count() + 1);
+
+        for layer in 0..self.layer_count() {
+            let name = format!("layer-{:0width$}.{}", layer, format, width = digits);
+            let path = dir.join(name);
+            fs::write(&path, self.export_layer(layer, scale, format))?;
+            paths.push(path);
+        }
+
+        let path = dir.join(format!("image.{}", format));
+        fs::write(&path, self.export(scale, format))?;
+        paths.push(path);
+
+        Ok(paths)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn image() -> SpaceImage {
+        SpaceImage::parse("0222112222120000", 2, 2).unwrap()
+    }
+
+    #[test]
+    fn netpbm() {
+        let image = image();
+        assert_eq!(
+            image.export(1, Format::Pgm),
+            b"P5\n2 2\n255\n\x00\xff\xff\x00".to_vec()
+        );
+        assert_eq!(
+            image.export_layer(0, 2, Format::Pgm)[11..],
+            [0, 0, 128, 128, 0, 0, 128, 128, 128, 128, 128, 128, 128, 128, 128, 128]
+        );
+
+        let ppm = image.export(3, Format::Ppm);
+        assert!(ppm.starts_with(b"P6\n6 6\n255\n"));
+        assert_eq!(ppm.len(), 11 + 6 * 6 * 3);
+    }
+
+    #[test]
+    fn png() {
+        let png = image().export(1, Format::Png);
+        assert!(png.starts_with(b"\x89PNG\r\n\x1a\n\x00\x00\x00\x0dIHDR"));
+        assert_eq!(png[16..24], [0, 0, 0, 2, 0, 0, 0, 2]);
+        assert!(png.ends_with(b"IEND\xae\x42\x60\x82"));
+        assert_eq!(crc32(b"123456789"), 0xcbf4_3926);
+        assert_eq!(adler32(b"Wikipedia"), 0x11e6_0398);
+    }
+
+    #[test]
+    fn save() {
+        let dir = std::env::temp_dir().join(format!("day8-export-{}", std::process::id()));
+        let paths = image().save(&dir, 4, Format::Ppm).unwrap();
+        let names: Vec<_> = paths
+            .iter()
+            .map(|p| p.file_name().unwrap().to_str().unwrap())
+            .collect();
+        assert_eq!(
+            names,
+            [
+                "layer-0.ppm",
+                "layer-1.ppm",
+                "layer-2.ppm",
+                "layer-3.ppm",
+                "image.ppm"
+            ]
+        );
+        assert!(paths.iter().all(|p| p.exists()));
+        fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn format_names() {
+        assert_eq!("PNG".parse(), Ok(Format::Png));
+        assert_eq!(Format::Pgm.to_string(), "pgm");
+        assert!("gif".parse::<Format>().is_err());
+    }
+}
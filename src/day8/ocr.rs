@@ -0,0 +1,93 @@
+use super::WHITE;
+
+pub const GLYPH_WIDTH: usize = 4;
+pub const GLYPH_HEIGHT: usize = 6;
+
+// The letters that show up in puzzle answers, drawn in the 4x6 font.
+const GLYPHS: [(char, &str); 18] = [
+    ('A', ".##.#..##..######..##..#"),
+    ('B', "###.#..####.#..##..####."),
+    ('C', ".##.#..##...#...#..#.##."),
+    ('E', "#####...###.#...#...####"),
+    ('F', "#####...###.#...#...#..."),
+    ('G', ".##.#..##...#.###..#.###"),
+    ('H', "#..##..######..##..##..#"),
+    ('I', ".###..#...#...#...#..###"),
+    ('J', "..##...#...#...##..#.##."),
+    ('K', "#..##.#.##..#.#.#.#.#..#"),
+    ('L', "#...#...#...#...#...####"),
+    ('O', ".##.#..##..##..##..#.##."),
+    ('P', "###.#..##..####.#...#..."),
+    ('R', "###.#..##..####.#.#.#..#"),
+    ('S', ".####...#....##....####."),
+    ('U', "#..##..##..##..##..#.##."),
+    ('Y', "#...#....#.#..#...#...#."),
+    ('Z', "####...#..#..#..#...####"),
+];
+
+// Reads the letters off a decoded image that is one glyph high. Glyphs are
+// four pixels wide with a one pixel gap between them.
+pub fn recognize(pixels: &[u32], width: usize) -> Result<String, String> {
+    if width == 0 || pixels.len() != width * GLYPH_HEIGHT {
+        return Err(format!("text must be {} pixels high", GLYPH_HEIGHT));
+    }
+
+    let at = |x: usize, y: usize| x < width && pixels[y * width + x] == WHITE;
+    let mut text = String::new();
+
+    for left in (0..width).step_by(GLYPH_WIDTH + 1) {
+        let glyph: String = (0..GLYPH_HEIGHT)
+            .flat_map(|y| (left..left + GLYPH_WIDTH).map(move |x| (x, y)))
+            .map(|(x, y)| if at(x, y) { '#' } else { '.' })
+            .collect();
+
+        if !glyph.contains('#') {
+            text.push(' ');
+            continue;
+        }
+
+        let letter = GLYPHS
+            .iter()
+            .find(|(_, shape)| *shape == glyph)
+            .map(|&(letter, _)| letter)
+            .ok_or_else(|| format!("unknown glyph at column {}", left + 1))?;
+        text.push(letter);
+    }
+
+    Ok(text.trim().to_string())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn pixels(rows: &[&str]) -> Vec<u32> {
+        rows.iter()
+            .flat_map(|row| row.chars())
+            .map(|c| if c == '#' { 1 } else { 0 })
+            .collect()
+    }
+
+    #[test]
+    fn letters() {
+        let image = pixels(&[
+            "#..#.####.#....#.....##..",
+            "#..#.#....#....#....#..#.",
+            "####.###..#....#....#..#.",
+            "#..#.#....#....#....#..#.",
+            "#..#.#....#....#....#..#.",
+            "#..#.####.####.####..##..",
+        ]);
+        assert_eq!(recognize(&image, 25), Ok(String::from("HELLO")));
+    }
+
+    #[test]
+    fn unknown_glyph() {
+        let image = pixels(&["#...#", "....#", "....#", "....#", "....#", "#...#"]);
+        assert_eq!(
+            recognize(&image, 5),
+            Err(String::from("unknown glyph at column 1"))
+        );
+        assert!(recognize(&image[..5], 5).is_err());
+    }
+}
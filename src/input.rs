@@ -0,0 +1,19 @@
+use std::fmt;
+
+// An error at byte `offset` of `line`, the `row`th line of the input counting
+// from zero, reported with its 1-based line and column.
+pub fn error_at(row: usize, line: &str, offset: usize, msg: impl fmt::Display) -> String {
+    let column = line[..offset].chars().count() + 1;
+    format!("line {}, column {}: {}", row + 1, column, msg)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn columns_count_characters() {
+        assert_eq!(error_at(0, "abc", 0, "oops"), "line 1, column 1: oops");
+        assert_eq!(error_at(2, "é,x", 3, "bad"), "line 3, column 3: bad");
+    }
+}
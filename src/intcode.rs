@@ -0,0 +1,862 @@
+use std::cell::RefCell;
+use std::collections::HashMap;
+use std::collections::VecDeque;
+use std::fmt;
+use std::io::{self, BufRead};
+use std::ops::Range;
+use std::rc::Rc;
+use std::str::FromStr;
+use std::sync::mpsc::{Receiver, Sender};
+use std::time::Instant;
+
+use std::convert::TryFrom;
+
+use crate::input::error_at;
+use crate::xorshift::XorShift;
+
+pub mod fuzz;
+mod history;
+
+pub use self::history::Undo;
+
+// Parses comma separated values, which may run over several lines. Blank
+// lines and whitespace around values are ignored; errors give the 1-based
+// line and column of the offending value.
+pub fn parse_values<T: FromStr>(input: &str) -> Result<Vec<T>, String> {
+    let mut values = Vec::new();
+
+    for (row, line) in input.lines().enumerate() {
+        if line.trim().is_empty() {
+            continue;
+        }
+
+        let mut offset = 0;
+        for token in line.trim_end().split(',') {
+            let leading = token.len() - token.trim_start().len();
+            let value = token.trim();
+
+            let parsed = value.parse().map_err(|_| {
+                let what = if value.is_empty() {
+                    "missing value"
+                } else {
+                    "invalid value"
+                };
+                let msg = format!("{} \"{}\"", what, value);
+                error_at(row, line, offset + leading, msg)
+            })?;
+            values.push(parsed);
+            offset += token.len() + 1;
+        }
+    }
+
+    if values.is_empty() {
+        return Err(String::from("no values"));
+    }
+
+    Ok(values)
+}
+
+pub fn parse_program(input: &str) -> Result<Vec<i64>, String> {
+    parse_values(input)
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Mode {
+    Position,
+    Immediate,
+    Relative,
+}
+
+impl TryFrom<i64> for Mode {
+    type Error = String;
+
+    fn try_from(i: i64) -> Result<Mode, String> {
+        match i {
+            0 => Ok(Mode::Position),
+            1 => Ok(Mode::Immediate),
+            2 => Ok(Mode::Relative),
+            _ => Err(format!("unknown mode: {}", i)),
+        }
+    }
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Instruction {
+    One(Mode, Mode, Mode),
+    Two(Mode, Mode, Mode),
+    Three(Mode),
+    Four(Mode),
+    Five(Mode, Mode),
+    Six(Mode, Mode),
+    Seven(Mode, Mode, Mode),
+    Eight(Mode, Mode, Mode),
+    Nine(Mode),
+    Halt,
+}
+
+impl TryFrom<i64> for Instruction {
+    type Error = String;
+
+    fn try_from(i: i64) -> Result<Instruction, String> {
+        let a = (i / 10000) % 10;
+        let b = (i / 1000) % 10;
+        let c = (i / 100) % 10;
+        let d = (i / 10) % 10;
+        let e = i % 10;
+
+        match (a, b, c, d, e) {
+            (0, 0, 0, 9, 9) => Ok(Instruction::Halt),
+            (m3, m2, m1, 0, 1) => Ok(Instruction::One(
+                Mode::try_from(m1)?,
+                Mode::try_from(m2)?,
+                Mode::try_from(m3)?,
+            )),
+            (m3, m2, m1, 0, 2) => Ok(Instruction::Two(
+                Mode::try_from(m1)?,
+                Mode::try_from(m2)?,
+                Mode::try_from(m3)?,
+            )),
+            (_, _, m, 0, 3) => Ok(Instruction::Three(Mode::try_from(m)?)),
+            (_, _, m1, 0, 4) => Ok(Instruction::Four(Mode::try_from(m1)?)),
+            (_, m2, m1, 0, 5) => Ok(Instruction::Five(Mode::try_from(m1)?, Mode::try_from(m2)?)),
+            (_, m2, m1, 0, 6) => Ok(Instruction::Six(Mode::try_from(m1)?, Mode::try_from(m2)?)),
+            (m3, m2, m1, 0, 7) => Ok(Instruction::Seven(
+                Mode::try_from(m1)?,
+                Mode::try_from(m2)?,
+                Mode::try_from(m3)?,
+            )),
+            (m3, m2, m1, 0, 8) => Ok(Instruction::Eight(
+                Mode::try_from(m1)?,
+                Mode::try_from(m2)?,
+                Mode::try_from(m3)?,
+            )),
+            (_, _, m, 0, 9) => Ok(Instruction::Nine(Mode::try_from(m)?)),
+            _ => Err(format!("unknown instruction {}", i)),
+        }
+    }
+}
+
+// Input/output attached to an `Intcode` machine. Opcode 3 calls `read` and
+// opcode 4 calls `write`. Returning `None` from `read` pauses the machine
+// on the input instruction so it can be resumed once input is available.
+pub trait IoDevice {
+    fn read(&mut self) -> Option<i64>;
+    fn write(&mut self, value: i64);
+}
+
+impl<D: IoDevice + ?Sized> IoDevice for &mut D {
+    fn read(&mut self) -> Option<i64> {
+        (**self).read()
+    }
+
+    fn write(&mut self, value: i64) {
+        (**self).write(value)
+    }
+}
+
+// A single shared bus: reads pop from the front, writes push to the back.
+impl IoDevice for VecDeque<i64> {
+    fn read(&mut self) -> Option<i64> {
+        self.pop_front()
+    }
+
+    fn write(&mut self, value: i64) {
+        self.push_back(value)
+    }
+}
+
+#[derive(Clone, Debug, Default)]
+pub struct Queues {
+    pub input: VecDeque<i64>,
+    pub output: VecDeque<i64>,
+}
+
+impl Queues {
+    pub fn new<I: IntoIterator<Item = i64>>(input: I) -> Queues {
+        Queues {
+            input: input.into_iter().collect(),
+            output: VecDeque::new(),
+        }
+    }
+}
+
+impl IoDevice for Queues {
+    fn read(&mut self) -> Option<i64> {
+        self.input.pop_front()
+    }
+
+    fn write(&mut self, value: i64) {
+        self.output.push_back(value)
+    }
+}
+
+pub struct FnDevice<R, W> {
+    read: R,
+    write: W,
+}
+
+pub fn from_fn<R, W>(read: R, write: W) -> FnDevice<R, W>
+where
+    R: FnMut() -> Option<i64>,
+    W: FnMut(i64),
+{
+    FnDevice { read, write }
+}
+
+impl<R, W> IoDevice for FnDevice<R, W>
+where
+    R: FnMut() -> Option<i64>,
+    W: FnMut(i64),
+{
+    fn read(&mut self) -> Option<i64> {
+        (self.read)()
+    }
+
+    fn write(&mut self, value: i64) {
+        (self.write)(value)
+    }
+}
+
+// Blocks on the receiver for input, so the machine only pauses once every
+// sender has been dropped.
+pub struct Channel {
+    pub rx: Receiver<i64>,
+    pub tx: Sender<i64>,
+}
+
+impl IoDevice for Channel {
+    fn read(&mut self) -> Option<i64> {
+        self.rx.recv().ok()
+    }
+
+    fn write(&mut self, value: i64) {
+        // a hung-up receiver just means nobody is listening anymore
+        let _ = self.tx.send(value);
+    }
+}
+
+// Reads one integer per line from stdin and prints each output on its own line.
+pub struct Stdio;
+
+impl IoDevice for Stdio {
+    fn read(&mut self) -> Option<i64> {
+        let mut line = String::new();
+        io::stdin().lock().read_line(&mut line).ok()?;
+        line.trim().parse().ok()
+    }
+
+    fn write(&mut self, value: i64) {
+        println!("{}", value);
+    }
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Event {
+    Read(i64),
+    Write(i64),
+}
+
+// Wraps another device and records every value passing through it.
+pub struct Transcript<D> {
+    pub device: D,
+    pub events: Vec<Event>,
+}
+
+impl<D: IoDevice> Transcript<D> {
+    pub fn new(device: D) -> Transcript<D> {
+        Transcript {
+            device,
+            events: Vec::new(),
+        }
+    }
+
+    pub fn outputs(&self) -> impl Iterator<Item = i64> + '_ {
+        self.events.iter().filter_map(|e| match e {
+            Event::Write(v) => Some(*v),
+            Event::Read(_) => None,
+        })
+    }
+}
+
+impl<D: IoDevice> IoDevice for Transcript<D> {
+    fn read(&mut self) -> Option<i64> {
+        let value = self.device.read()?;
+        self.events.push(Event::Read(value));
+        Some(value)
+    }
+
+    fn write(&mut self, value: i64) {
+        self.events.push(Event::Write(value));
+        self.device.write(value)
+    }
+}
+
+// Rust code attached to a range of Intcode addresses. `offset` is relative
+// to the start of the mapped range.
+pub trait MappedDevice {
+    fn load(&mut self, offset: usize) -> i64;
+    fn store(&mut self, offset: usize, value: i64);
+
+    // How many addresses the device backs, or None if it answers at any
+    // offset. Mappings can't be larger than this.
+    fn size(&self) -> Option<usize> {
+        None
+    }
+}
+
+// Lets the caller keep a handle on a device after mapping it.
+impl<D: MappedDevice + ?Sized> MappedDevice for Rc<RefCell<D>> {
+    fn load(&mut self, offset: usize) -> i64 {
+        self.borrow_mut().load(offset)
+    }
+
+    fn store(&mut self, offset: usize, value: i64) {
+        self.borrow_mut().store(offset, value)
+    }
+
+    fn size(&self) -> Option<usize> {
+        self.borrow().size()
+    }
+}
+
+// Plain RAM bank.
+impl MappedDevice for Vec<i64> {
+    fn load(&mut self, offset: usize) -> i64 {
+        self[offset]
+    }
+
+    fn store(&mut self, offset: usize, value: i64) {
+        self[offset] = value
+    }
+
+    fn size(&self) -> Option<usize> {
+        Some(self.len())
+    }
+}
+
+pub struct Framebuffer {
+    pub width: usize,
+    pub height: usize,
+    pub pixels: Vec<i64>,
+}
+
+impl Framebuffer {
+    pub fn new(width: usize, height: usize) -> Framebuffer {
+        Framebuffer {
+            width,
+            height,
+            pixels: vec![0; width * height],
+        }
+    }
+}
+
+impl MappedDevice for Framebuffer {
+    fn load(&mut self, offset: usize) -> i64 {
+        self.pixels[offset]
+    }
+
+    fn store(&mut self, offset: usize, value: i64) {
+        self.pixels[offset] = value
+    }
+
+    fn size(&self) -> Option<usize> {
+        Some(self.pixels.len())
+    }
+}
+
+impl fmt::Display for Framebuffer {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        for row in self.pixels.chunks(self.width) {
+            for px in row {
+                write!(f, "{}", if *px == 0 { ' ' } else { '#' })?;
+            }
+            writeln!(f)?;
+        }
+        Ok(())
+    }
+}
+
+// xorshift64 generator. Every load yields a fresh non-negative number and a
+// store reseeds it.
+pub struct Random {
+    rng: XorShift,
+}
+
+impl Random {
+    pub fn new(seed: u64) -> Random {
+        Random {
+            rng: XorShift::new(seed),
+        }
+    }
+}
+
+impl MappedDevice for Random {
+    fn load(&mut self, _: usize) -> i64 {
+        (self.rng.next_u64() >> 1) as i64
+    }
+
+    fn store(&mut self, _: usize, value: i64) {
+        self.rng = XorShift::new(value as u64);
+    }
+}
+
+// Milliseconds since the clock was created; stores are ignored.
+pub struct Clock {
+    start: Instant,
+}
+
+impl Clock {
+    pub fn new() -> Clock {
+        Clock {
+            start: Instant::now(),
+        }
+    }
+}
+
+impl Default for Clock {
+    fn default() -> Clock {
+        Clock::new()
+    }
+}
+
+impl MappedDevice for Clock {
+    fn load(&mut self, _: usize) -> i64 {
+        self.start.elapsed().as_millis() as i64
+    }
+
+    fn store(&mut self, _: usize, _: i64) {}
+}
+
+struct Mapping {
+    range: Range<usize>,
+    device: Box<dyn MappedDevice>,
+}
+
+pub struct Intcode {
+    pub memory: HashMap<usize, i64>,
+    pub pc: usize,
+    pub relative_base: i64,
+    pub halted: bool,
+    // why the last instruction failed, if it did
+    pub error: Option<String>,
+    pub cycles: usize,
+    mappings: Vec<Mapping>,
+    history: Option<Vec<Undo>>,
+}
+
+impl Intcode {
+    pub fn new(memory: &[i64]) -> Intcode {
+        Intcode {
+            memory: memory.iter().enumerate().map(|(k, v)| (k, *v)).collect(),
+            pc: 0,
+            relative_base: 0,
+            halted: false,
+            error: None,
+            cycles: 0,
+            mappings: Vec::new(),
+            history: None,
+        }
+    }
+
+    // Extension to the standard spec: data reads and writes inside `range`
+    // go to `device` instead of `memory`. Opcodes and their raw parameters
+    // are always fetched from `memory`.
+    pub fn map<D: MappedDevice + 'static>(
+        &mut self,
+        range: Range<usize>,
+        device: D,
+    ) -> Result<(), String> {
+        if range.start >= range.end {
+            return Err(format!("empty mapping {:?}", range));
+        }
+
+        if let Some(size) = device.size().filter(|&size| size < range.len()) {
+            return Err(format!(
+                "mapping {:?} is larger than its device of {} words",
+                range, size
+            ));
+        }
+
+        if let Some(m) = self
+            .mappings
+            .iter()
+            .find(|m| range.start < m.range.end && m.range.start < range.end)
+        {
+            return Err(format!(
+                "mapping {:?} overlaps existing mapping {:?}",
+                range, m.range
+            ));
+        }
+
+        self.mappings.push(Mapping {
+            range,
+            device: Box::new(device),
+        });
+        Ok(())
+    }
+
+    pub fn unmap(&mut self, addr: usize) -> Option<Box<dyn MappedDevice>> {
+        let i = self.mappings.iter().position(|m| m.range.contains(&addr))?;
+        Some(self.mappings.remove(i).device)
+    }
+
+    // Runs until the program halts, a read finds no input available or an
+    // instruction fails.
+    pub fn run<D: IoDevice>(&mut self, mut io: D) {
+        while self.step(&mut io) {}
+    }
+
+    // Executes one instruction. Returns false without advancing once the
+    // program has halted, is waiting for input or has failed; a failed
+    // instruction leaves its reason in `error`.
+    pub fn step<D: IoDevice>(&mut self, mut io: D) -> bool {
+        if self.halted || self.error.is_some() {
+            return false;
+        }
+
+        if let Some(history) = &mut self.history {
+            history.push(Undo {
+                pc: self.pc,
+                relative_base: self.relative_base,
+                write: None,
+            });
+        }
+
+        match self.execute(&mut io) {
+            Ok(true) => {
+                self.cycles += 1;
+                true
+            }
+            Ok(false) => false,
+            Err(e) => {
+                if let Some(history) = &mut self.history {
+                    history.pop();
+                }
+                self.error = Some(format!("{} at pc {}", e, self.pc));
+                false
+            }
+        }
+    }
+
+    fn execute<D: IoDevice>(&mut self, io: &mut D) -> Result<bool, String> {
+        match Instruction::try_from(self.peek(self.pc))? {
+            Instruction::Halt => {
+                self.halted = true;
+                self.cycles += 1;
+                return Ok(false);
+            }
+            Instruction::One(m1, m2, m3) => {
+                let p1 = self.value(m1, self.peek(self.pc + 1))?;
+                let p2 = self.value(m2, self.peek(self.pc + 2))?;
+                let p3 = self.address(m3, self.peek(self.pc + 3))?;
+                self.write(p3, p1 + p2);
+                self.pc += 4;
+            }
+            Instruction::Two(m1, m2, m3) => {
+                let p1 = self.value(m1, self.peek(self.pc + 1))?;
+                let p2 = self.value(m2, self.peek(self.pc + 2))?;
+                let p3 = self.address(m3, self.peek(self.pc + 3))?;
+                self.write(p3, p1 * p2);
+                self.pc += 4;
+            }
+            Instruction::Three(m) => {
+                let p1 = self.address(m, self.peek(self.pc + 1))?;
+                match io.read() {
+                    Some(input) => {
+                        self.write(p1, input);
+                        self.pc += 2;
+                    }
+                    None => {
+                        if let Some(history) = &mut self.history {
+                            history.pop();
+                        }
+                        return Ok(false);
+                    }
+                }
+            }
+            Instruction::Four(m) => {
+                let p1 = self.value(m, self.peek(self.pc + 1))?;
+                io.write(p1);
+                self.pc += 2;
+            }
+            Instruction::Five(m1, m2) => {
+                let p1 = self.value(m1, self.peek(self.pc + 1))?;
+                let p2 = self.value(m2, self.peek(self.pc + 2))?;
+
+                if p1 != 0 {
+                    self.pc = to_address(p2)?;
+                } else {
+                    self.pc += 3;
+                }
+            }
+            Instruction::Six(m1, m2) => {
+                let p1 = self.value(m1, self.peek(self.pc + 1))?;
+                let p2 = self.value(m2, self.peek(self.pc + 2))?;
+
+                if p1 == 0 {
+                    self.pc = to_address(p2)?;
+                } else {
+                    self.pc += 3;
+                }
+            }
+            Instruction::Seven(m1, m2, m3) => {
+                let p1 = self.value(m1, self.peek(self.pc + 1))?;
+                let p2 = self.value(m2, self.peek(self.pc + 2))?;
+                let p3 = self.address(m3, self.peek(self.pc + 3))?;
+
+                if p1 < p2 {
+                    self.write(p3, 1);
+                } else {
+                    self.write(p3, 0);
+                }
+
+                self.pc += 4;
+            }
+            Instruction::Eight(m1, m2, m3) => {
+                let p1 = self.value(m1, self.peek(self.pc + 1))?;
+                let p2 = self.value(m2, self.peek(self.pc + 2))?;
+                let p3 = self.address(m3, self.peek(self.pc + 3))?;
+
+                if p1 == p2 {
+                    self.write(p3, 1);
+                } else {
+                    self.write(p3, 0);
+                }
+
+                self.pc += 4;
+            }
+            Instruction::Nine(m) => {
+                self.relative_base += self.value(m, self.peek(self.pc + 1))?;
+                self.pc += 2;
+            }
+        }
+
+        Ok(true)
+    }
+
+    pub fn peek(&self, addr: usize) -> i64 {
+        *self.memory.get(&addr).unwrap_or(&0)
+    }
+
+    pub fn load(&mut self, addr: usize) -> i64 {
+        match self.mappings.iter_mut().find(|m| m.range.contains(&addr)) {
+            Some(m) => m.device.load(addr - m.range.start),
+            None => self.peek(addr),
+        }
+    }
+
+    pub fn store(&mut self, addr: usize, value: i64) {
+        self.store_memory(addr, value);
+    }
+
+    // Returns the previous contents when the store went to plain memory.
+    fn store_memory(&mut self, addr: usize, value: i64) -> Option<Option<i64>> {
+        match self.mappings.iter_mut().find(|m| m.range.contains(&addr)) {
+            Some(m) => {
+                m.device.store(addr - m.range.start, value);
+                None
+            }
+            None => Some(self.memory.insert(addr, value)),
+        }
+    }
+
+    // A store made by the current instruction, recorded in the undo log.
+    fn write(&mut self, addr: usize, value: i64) {
+        let previous = self.store_memory(addr, value);
+
+        if let Some(undo) = self.history.as_mut().and_then(|h| h.last_mut()) {
+            undo.write = previous.map(|p| (addr, p));
+        }
+    }
+
+    fn value(&mut self, mode: Mode, value: i64) -> Result<i64, String> {
+        match mode {
+            Mode::Immediate => Ok(value),
+            _ => {
+                let addr = self.address(mode, value)?;
+                Ok(self.load(addr))
+            }
+        }
+    }
+
+    fn address(&self, mode: Mode, addr: i64) -> Result<usize, String> {
+        match mode {
+            Mode::Relative => to_address(addr + self.relative_base),
+            _ => to_address(addr),
+        }
+    }
+}
+
+fn to_address(addr: i64) -> Result<usize, String> {
+    usize::try_from(addr).map_err(|_| format!("negative address {}", addr))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::sync::mpsc;
+    use std::thread;
+
+    #[test]
+    fn quine() {
+        let program =
+            parse_program("109,1,204,-1,1001,100,1,100,1008,100,16,101,1006,101,0,99").unwrap();
+        let mut io = Queues::default();
+        Intcode::new(&program).run(&mut io);
+        assert_eq!(io.output.into_iter().collect::<Vec<_>>(), program);
+    }
+
+    #[test]
+    fn invalid_instructions() {
+        let mut computer = Intcode::new(&[42]);
+        computer.run(Queues::default());
+        assert!(!computer.halted);
+        assert_eq!(
+            computer.error.as_deref(),
+            Some("unknown instruction 42 at pc 0")
+        );
+        assert!(!computer.step(Queues::default()));
+        assert_eq!(computer.cycles, 0);
+
+        for (program, error) in &[
+            (vec![1, -1, 0, 0, 99], "negative address -1 at pc 0"),
+            (vec![1101, 1, 1, -3, 99], "negative address -3 at pc 0"),
+            (vec![1105, 1, -2], "negative address -2 at pc 0"),
+            (
+                vec![109, -1, 22201, 0, 0, 0, 99],
+                "negative address -1 at pc 2",
+            ),
+            (vec![302, 0, 0, 0], "unknown mode: 3 at pc 0"),
+        ] {
+            let mut computer = Intcode::new(program);
+            computer.run(Queues::default());
+            assert_eq!(computer.error.as_deref(), Some(*error));
+        }
+    }
+
+    #[test]
+    fn pauses_without_input() {
+        let program = parse_program("3,11,3,12,1,11,12,13,4,13,99").unwrap();
+        let mut computer = Intcode::new(&program);
+        let mut io = Queues::new(vec![3]);
+
+        computer.run(&mut io);
+        assert!(!computer.halted);
+        assert_eq!(computer.pc, 2);
+
+        io.input.push_back(4);
+        computer.run(&mut io);
+        assert!(computer.halted);
+        assert_eq!(io.output.pop_front(), Some(7));
+    }
+
+    #[test]
+    fn closure_and_transcript() {
+        let program = parse_program("3,0,4,0,99").unwrap();
+        let mut seen = Vec::new();
+        let mut io = Transcript::new(from_fn(|| Some(42), |v| seen.push(v)));
+        Intcode::new(&program).run(&mut io);
+        assert_eq!(io.events, vec![Event::Read(42), Event::Write(42)]);
+        assert_eq!(io.outputs().collect::<Vec<_>>(), vec![42]);
+        drop(io);
+        assert_eq!(seen, vec![42]);
+    }
+
+    #[test]
+    fn channels() {
+        let program = parse_program("3,20,102,2,20,20,4,20,1105,1,0").unwrap();
+        let (in_tx, in_rx) = mpsc::channel();
+        let (out_tx, out_rx) = mpsc::channel();
+
+        let handle = thread::spawn(move || {
+            let mut computer = Intcode::new(&program);
+            computer.run(Channel {
+                rx: in_rx,
+                tx: out_tx,
+            });
+            computer.halted
+        });
+
+        for i in 1..=3 {
+            in_tx.send(i).unwrap();
+            assert_eq!(out_rx.recv(), Ok(2 * i));
+        }
+        drop(in_tx);
+
+        assert!(!handle.join().unwrap());
+    }
+
+    #[test]
+    fn memory_mapped_framebuffer() {
+        // copy the 4 words at 100.. into the framebuffer at 1000..
+        let mut program =
+            parse_program("1101,0,0,1000,101,0,101,1001,1001,102,0,1002,1001,103,0,1003,99")
+                .unwrap();
+        program.resize(100, 0);
+        program.extend(&[7, 0, 5, 9]);
+
+        let screen = Rc::new(RefCell::new(Framebuffer::new(2, 2)));
+        let mut computer = Intcode::new(&program);
+        computer.map(1000..1004, screen.clone()).unwrap();
+        computer.run(Queues::default());
+
+        assert_eq!(screen.borrow().pixels, vec![0, 0, 5, 9]);
+        assert_eq!(screen.borrow().to_string(), "  \n##\n");
+        assert!(!computer.memory.contains_key(&1002));
+    }
+
+    #[test]
+    fn memory_mapped_random() {
+        let program = parse_program("4,500,4,500,99").unwrap();
+        let mut io = Queues::default();
+        let mut computer = Intcode::new(&program);
+        computer.map(500..501, Random::new(1)).unwrap();
+        computer.run(&mut io);
+
+        let mut rng = Random::new(1);
+        let expected: VecDeque<i64> = (0..2).map(|_| rng.load(0)).collect();
+        assert_eq!(io.output, expected);
+    }
+
+    #[test]
+    fn oversized_mappings() {
+        let mut computer = Intcode::new(&[99]);
+        assert_eq!(
+            computer.map(10..20, vec![0; 2]),
+            Err(String::from(
+                "mapping 10..20 is larger than its device of 2 words"
+            ))
+        );
+        let screen = Rc::new(RefCell::new(Framebuffer::new(2, 2)));
+        assert!(computer.map(10..15, screen.clone()).is_err());
+        assert!(computer.map(10..14, screen).is_ok());
+        assert!(computer.map(20..30, Clock::new()).is_ok());
+    }
+
+    #[test]
+    fn overlapping_mappings() {
+        let mut computer = Intcode::new(&[99]);
+        computer.map(10..20, vec![0; 10]).unwrap();
+        assert!(computer.map(19..21, vec![0; 2]).is_err());
+        assert!(computer.map(5..5, vec![]).is_err());
+        assert!(computer.map(20..22, vec![0; 2]).is_ok());
+        assert!(computer.unmap(15).is_some());
+        assert!(computer.map(5..15, vec![0; 10]).is_ok());
+    }
+
+    #[test]
+    fn malformed_programs() {
+        assert_eq!(parse_program("1,2,3\n"), Ok(vec![1, 2, 3]));
+        assert_eq!(parse_program(" 1, 2\n3 \n\n"), Ok(vec![1, 2, 3]));
+        assert_eq!(
+            parse_program("1,2,x,4"),
+            Err(String::from("line 1, column 5: invalid value \"x\""))
+        );
+        assert_eq!(
+            parse_program("1,2\n3,,4"),
+            Err(String::from("line 2, column 3: missing value \"\""))
+        );
+        assert_eq!(parse_program("\n \n"), Err(String::from("no values")));
+        assert!(parse_values::<u32>("1,-2").is_err());
+    }
+}
@@ -0,0 +1,985 @@
+use std::collections::hash_map::DefaultHasher;
+use std::fmt;
+use std::fs;
+use std::hash::{Hash, Hasher};
+use std::io;
+use std::panic::{self, AssertUnwindSafe};
+use std::path::{Path, PathBuf};
+use std::str::FromStr;
+
+use super::{parse_program, Intcode, Queues};
+use crate::day2;
+use crate::xorshift::XorShift;
+
+const STEP_LIMIT: usize = 1_000_000;
+const INPUT_BOUND: i64 = 50;
+const MAX_DEPTH: usize = 2;
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub enum Profile {
+    // every opcode and addressing mode, with bounded loops and I/O
+    Full,
+    // position-mode add/mul only, the subset day2's interpreter understands
+    Day2,
+}
+
+impl Profile {
+    fn limit(self) -> i128 {
+        match self {
+            Profile::Full => 1 << 40,
+            Profile::Day2 => i128::from(u32::MAX),
+        }
+    }
+}
+
+impl fmt::Display for Profile {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Profile::Full => write!(f, "full"),
+            Profile::Day2 => write!(f, "day2"),
+        }
+    }
+}
+
+impl FromStr for Profile {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "full" => Ok(Profile::Full),
+            "day2" => Ok(Profile::Day2),
+            _ => Err(format!("unknown profile \"{}\"", s)),
+        }
+    }
+}
+
+// Positions and relative offsets index the program's data region.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Operand {
+    Immediate(i64),
+    Position(usize),
+    Relative(usize),
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Target {
+    Position(usize),
+    Relative(usize),
+}
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum Stmt {
+    // opcode 1, 2, 7 or 8
+    Arith(i64, Operand, Operand, Target),
+    Input(Target),
+    Output(Operand),
+    // runs the body a fixed number of times using a counter outside the data region
+    Loop(i64, Vec<Stmt>),
+    // jumps over the body when the operand is non-zero (true) or zero (false)
+    Skip(bool, Operand, Vec<Stmt>),
+}
+
+impl Stmt {
+    fn len(&self) -> usize {
+        match self {
+            Stmt::Arith(..) => 4,
+            Stmt::Input(_) | Stmt::Output(_) => 2,
+            Stmt::Loop(_, body) => 4 + block_len(body) + 4 + 3,
+            Stmt::Skip(_, _, body) => 3 + block_len(body),
+        }
+    }
+
+    fn depth(&self) -> usize {
+        match self {
+            Stmt::Loop(_, body) => 1 + block_depth(body),
+            Stmt::Skip(_, _, body) => block_depth(body),
+            _ => 0,
+        }
+    }
+}
+
+fn block_len(block: &[Stmt]) -> usize {
+    block.iter().map(Stmt::len).sum()
+}
+
+fn block_depth(block: &[Stmt]) -> usize {
+    block.iter().map(Stmt::depth).max().unwrap_or(0)
+}
+
+// A structured random program. Every write lands in the data region and
+// every loop is counted, so a correct engine always halts.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Program {
+    pub profile: Profile,
+    pub body: Vec<Stmt>,
+    pub data: Vec<i64>,
+    pub input: Vec<i64>,
+}
+
+struct Bounds {
+    cells: Vec<i128>,
+    input: i128,
+    reads: usize,
+    limit: i128,
+}
+
+impl Bounds {
+    fn operand(&self, op: Operand) -> i128 {
+        match op {
+            Operand::Immediate(v) => i128::from(v).abs(),
+            Operand::Position(i) | Operand::Relative(i) => self.cells[i],
+        }
+    }
+
+    fn set(&mut self, t: Target, value: i128) -> bool {
+        match t {
+            Target::Position(i) | Target::Relative(i) => self.cells[i] = value,
+        }
+        value <= self.limit
+    }
+
+    // Tracks the largest magnitude each cell can hold. Returns false once any
+    // value could exceed the profile's limit.
+    fn apply(&mut self, block: &[Stmt]) -> bool {
+        for stmt in block {
+            let ok = match stmt {
+                Stmt::Arith(op, a, b, t) => {
+                    let (a, b) = (self.operand(*a), self.operand(*b));
+                    let value = match op {
+                        1 => a.saturating_add(b),
+                        2 => a.saturating_mul(b),
+                        _ => 1,
+                    };
+                    self.set(*t, value)
+                }
+                Stmt::Input(t) => {
+                    self.reads += 1;
+                    self.set(*t, self.input)
+                }
+                Stmt::Output(_) => true,
+                Stmt::Loop(k, body) => (0..*k).all(|_| self.apply(body)),
+                Stmt::Skip(_, _, body) => {
+                    let before = self.cells.clone();
+                    let ok = self.apply(body);
+                    for (c, b) in self.cells.iter_mut().zip(before) {
+                        *c = (*c).max(b);
+                    }
+                    ok
+                }
+            };
+
+            if !ok {
+                return false;
+            }
+        }
+
+        true
+    }
+}
+
+impl Program {
+    fn bounds(&self, input: i128) -> Option<Bounds> {
+        let mut bounds = Bounds {
+            cells: self.data.iter().map(|v| i128::from(*v).abs()).collect(),
+            input,
+            reads: 0,
+            limit: self.profile.limit(),
+        };
+
+        if bounds.apply(&self.body) {
+            Some(bounds)
+        } else {
+            None
+        }
+    }
+
+    pub fn is_valid(&self) -> bool {
+        let input = self.input.iter().map(|v| i128::from(*v).abs()).max();
+        self.bounds(input.unwrap_or(0)).is_some()
+    }
+
+    pub fn assemble(&self) -> Case {
+        let prologue = match self.profile {
+            Profile::Full => 2,
+            Profile::Day2 => 0,
+        };
+        let counters = prologue + block_len(&self.body) + 1;
+        let data = counters + block_depth(&self.body);
+
+        let mut asm = Assembler {
+            code: Vec::new(),
+            counters,
+            data,
+        };
+
+        if self.profile == Profile::Full {
+            asm.code.extend(&[109, data as i64]);
+        }
+        asm.block(&self.body, 0);
+        asm.code.push(99);
+        asm.code.resize(data, 0);
+        asm.code.extend(&self.data);
+
+        Case {
+            profile: self.profile,
+            memory: asm.code,
+            input: self.input.clone(),
+        }
+    }
+
+    // Every program one step simpler than this one: a statement removed, a
+    // loop or skip inlined, a loop run once, or an immediate zeroed.
+    pub fn shrinks(&self) -> Vec<Program> {
+        let mut shrinks: Vec<Program> = shrink_block(&self.body)
+            .into_iter()
+            .map(|body| Program {
+                body,
+                ..self.clone()
+            })
+            .collect();
+
+        for i in 0..self.data.len() {
+            if self.data[i] != 0 {
+                let mut p = self.clone();
+                p.data[i] = 0;
+                shrinks.push(p);
+            }
+        }
+
+        if !self.input.is_empty() {
+            let mut p = self.clone();
+            p.input.pop();
+            shrinks.push(p);
+        }
+
+        shrinks
+    }
+}
+
+fn with_body(stmt: &Stmt, body: Vec<Stmt>) -> Stmt {
+    match stmt {
+        Stmt::Loop(k, _) => Stmt::Loop(*k, body),
+        Stmt::Skip(t, c, _) => Stmt::Skip(*t, *c, body),
+        _ => stmt.clone(),
+    }
+}
+
+fn shrink_block(block: &[Stmt]) -> Vec<Vec<Stmt>> {
+    let mut shrinks = Vec::new();
+    let replace = |i: usize, with: Vec<Stmt>| {
+        let mut b = block.to_vec();
+        b.splice(i..=i, with);
+        b
+    };
+
+    for (i, stmt) in block.iter().enumerate() {
+        shrinks.push(replace(i, vec![]));
+
+        match stmt {
+            Stmt::Loop(_, body) | Stmt::Skip(_, _, body) => {
+                shrinks.push(replace(i, body.clone()));
+                for inner in shrink_block(body) {
+                    shrinks.push(replace(i, vec![with_body(stmt, inner)]));
+                }
+            }
+            _ => {}
+        }
+
+        match stmt {
+            Stmt::Loop(k, body) if *k > 1 => {
+                shrinks.push(replace(i, vec![Stmt::Loop(1, body.clone())]));
+            }
+            Stmt::Arith(op, a, b, t) => {
+                if let Operand::Immediate(v) = a {
+                    if *v != 0 {
+                        let zeroed = Stmt::Arith(*op, Operand::Immediate(0), *b, *t);
+                        shrinks.push(replace(i, vec![zeroed]));
+                    }
+                }
+                if let Operand::Immediate(v) = b {
+                    if *v != 0 {
+                        let zeroed = Stmt::Arith(*op, *a, Operand::Immediate(0), *t);
+                        shrinks.push(replace(i, vec![zeroed]));
+                    }
+                }
+            }
+            Stmt::Output(Operand::Immediate(v)) if *v != 0 => {
+                shrinks.push(replace(i, vec![Stmt::Output(Operand::Immediate(0))]));
+            }
+            _ => {}
+        }
+    }
+
+    shrinks
+}
+
+struct Assembler {
+    code: Vec<i64>,
+    counters: usize,
+    data: usize,
+}
+
+impl Assembler {
+    fn mode(op: Operand) -> i64 {
+        match op {
+            Operand::Position(_) => 0,
+            Operand::Immediate(_) => 1,
+            Operand::Relative(_) => 2,
+        }
+    }
+
+    fn target_mode(t: Target) -> i64 {
+        match t {
+            Target::Position(_) => 0,
+            Target::Relative(_) => 2,
+        }
+    }
+
+    fn operand(&self, op: Operand) -> i64 {
+        match op {
+            Operand::Immediate(v) => v,
+            Operand::Position(i) => (self.data + i) as i64,
+            Operand::Relative(i) => i as i64,
+        }
+    }
+
+    fn target(&self, t: Target) -> i64 {
+        match t {
+            Target::Position(i) => (self.data + i) as i64,
+            Target::Relative(i) => i as i64,
+        }
+    }
+
+    fn block(&mut self, block: &[Stmt], depth: usize) {
+        for stmt in block {
+            self.stmt(stmt, depth);
+        }
+    }
+
+    fn stmt(&mut self, stmt: &Stmt, depth: usize) {
+        match stmt {
+            Stmt::Arith(op, a, b, t) => {
+                let modes = 100 * Self::mode(*a) + 1000 * Self::mode(*b);
+                let opcode = op + modes + 10000 * Self::target_mode(*t);
+                let params = [self.operand(*a), self.operand(*b), self.target(*t)];
+                self.code.push(opcode);
+                self.code.extend(&params);
+            }
+            Stmt::Input(t) => {
+                let param = self.target(*t);
+                self.code.push(3 + 100 * Self::target_mode(*t));
+                self.code.push(param);
+            }
+            Stmt::Output(a) => {
+                let param = self.operand(*a);
+                self.code.push(4 + 100 * Self::mode(*a));
+                self.code.push(param);
+            }
+            Stmt::Loop(k, body) => {
+                let counter = (self.counters + depth) as i64;
+                self.code.extend(&[1101, *k, 0, counter]);
+                let start = self.code.len() as i64;
+                self.block(body, depth + 1);
+                self.code.extend(&[1001, counter, -1, counter]);
+                self.code.extend(&[1005, counter, start]);
+            }
+            Stmt::Skip(jump_if_true, cond, body) => {
+                let opcode = if *jump_if_true { 5 } else { 6 };
+                let param = self.operand(*cond);
+                self.code.push(opcode + 100 * Self::mode(*cond) + 1000);
+                self.code.push(param);
+                self.code.push(0);
+                let patch = self.code.len() - 1;
+                self.block(body, depth);
+                self.code[patch] = self.code.len() as i64;
+            }
+        }
+    }
+}
+
+pub struct Generator {
+    rng: XorShift,
+    profile: Profile,
+}
+
+impl Generator {
+    pub fn new(seed: u64, profile: Profile) -> Generator {
+        Generator {
+            rng: XorShift::new(seed),
+            profile,
+        }
+    }
+
+    fn below(&mut self, n: usize) -> usize {
+        self.rng.below(n)
+    }
+
+    fn between(&mut self, lo: i64, hi: i64) -> i64 {
+        lo + self.below((hi - lo + 1) as usize) as i64
+    }
+
+    fn operand(&mut self, data: usize) -> Operand {
+        match self.below(3) {
+            0 => Operand::Immediate(self.between(-10, 10)),
+            1 => Operand::Position(self.below(data)),
+            _ => Operand::Relative(self.below(data)),
+        }
+    }
+
+    fn target(&mut self, data: usize) -> Target {
+        match self.below(2) {
+            0 => Target::Position(self.below(data)),
+            _ => Target::Relative(self.below(data)),
+        }
+    }
+
+    fn stmt(&mut self, data: usize, depth: usize) -> Stmt {
+        if self.profile == Profile::Day2 {
+            let op = if self.below(2) == 0 { 1 } else { 2 };
+            return Stmt::Arith(
+                op,
+                Operand::Position(self.below(data)),
+                Operand::Position(self.below(data)),
+                Target::Position(self.below(data)),
+            );
+        }
+
+        match self.below(10) {
+            0..=3 => {
+                let op = [1, 2, 7, 8][self.below(4)];
+                Stmt::Arith(
+                    op,
+                    self.operand(data),
+                    self.operand(data),
+                    self.target(data),
+                )
+            }
+            4 => Stmt::Input(self.target(data)),
+            7 if depth < MAX_DEPTH => {
+                let k = self.between(1, 5);
+                let len = 1 + self.below(4);
+                Stmt::Loop(k, self.block(data, depth + 1, len))
+            }
+            8 => {
+                let jump_if_true = self.below(2) == 0;
+                let cond = self.operand(data);
+                let len = 1 + self.below(4);
+                Stmt::Skip(jump_if_true, cond, self.block(data, depth, len))
+            }
+            _ => Stmt::Output(self.operand(data)),
+        }
+    }
+
+    fn block(&mut self, data: usize, depth: usize, len: usize) -> Vec<Stmt> {
+        (0..len).map(|_| self.stmt(data, depth)).collect()
+    }
+
+    pub fn program(&mut self) -> Program {
+        let data_len = 1 + self.below(8);
+        let data = (0..data_len)
+            .map(|_| match self.profile {
+                Profile::Full => self.between(-20, 20),
+                Profile::Day2 => self.between(0, 10),
+            })
+            .collect();
+
+        let mut program = Program {
+            profile: self.profile,
+            body: Vec::new(),
+            data,
+            input: Vec::new(),
+        };
+
+        for _ in 0..1 + self.below(20) {
+            // a statement that could overflow is retried a few times, then dropped
+            for _ in 0..4 {
+                let stmt = self.stmt(data_len, 0);
+                program.body.push(stmt);
+                if program.bounds(i128::from(INPUT_BOUND)).is_some() {
+                    break;
+                }
+                program.body.pop();
+            }
+        }
+
+        let reads = program.bounds(i128::from(INPUT_BOUND)).unwrap().reads;
+        program.input = (0..reads)
+            .map(|_| self.between(-INPUT_BOUND, INPUT_BOUND))
+            .collect();
+        program
+    }
+}
+
+// An assembled program and its input, as handed to each engine.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Case {
+    pub profile: Profile,
+    pub memory: Vec<i64>,
+    pub input: Vec<i64>,
+}
+
+fn join(values: &[i64]) -> String {
+    let values: Vec<String> = values.iter().map(|v| v.to_string()).collect();
+    values.join(",")
+}
+
+impl fmt::Display for Case {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        writeln!(f, "profile: {}", self.profile)?;
+        writeln!(f, "memory: {}", join(&self.memory))?;
+        writeln!(f, "input: {}", join(&self.input))
+    }
+}
+
+impl FromStr for Case {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let mut profile = None;
+        let mut memory = None;
+        let mut input = Vec::new();
+
+        for line in s.lines().filter(|l| !l.trim().is_empty()) {
+            let mut parts = line.splitn(2, ':');
+            let key = parts.next().unwrap().trim();
+            let value = parts.next().map(str::trim).unwrap_or("");
+
+            match key {
+                "profile" => profile = Some(value.parse()?),
+                "memory" => memory = Some(parse_program(value)?),
+                "input" if !value.is_empty() => input = parse_program(value)?,
+                "input" => {}
+                _ => return Err(format!("unknown key \"{}\"", key)),
+            }
+        }
+
+        Ok(Case {
+            profile: profile.ok_or("missing profile")?,
+            memory: memory.ok_or("missing memory")?,
+            input,
+        })
+    }
+}
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Outcome {
+    pub outputs: Vec<i64>,
+    pub halted: bool,
+    // final contents of every address in the original image
+    pub memory: Vec<i64>,
+}
+
+pub trait Engine {
+    fn name(&self) -> &'static str;
+
+    fn supports(&self, _case: &Case) -> bool {
+        true
+    }
+
+    fn execute(&self, case: &Case) -> Outcome;
+}
+
+pub struct Reference;
+
+impl Engine for Reference {
+    fn name(&self) -> &'static str {
+        "intcode"
+    }
+
+    fn execute(&self, case: &Case) -> Outcome {
+        let mut computer = Intcode::new(&case.memory);
+        let mut io = Queues::new(case.input.iter().copied());
+
+        for _ in 0..STEP_LIMIT {
+            if !computer.step(&mut io) {
+                break;
+            }
+        }
+
+        if let Some(e) = computer.error {
+            panic!("{}", e);
+        }
+
+        Outcome {
+            outputs: io.output.into_iter().collect(),
+            halted: computer.halted,
+            memory: (0..case.memory.len()).map(|a| computer.peek(a)).collect(),
+        }
+    }
+}
+
+// A second, deliberately naive interpreter over a flat `Vec` that grows on
+// demand, written independently of `Intcode` so the two can be compared on
+// the full instruction set.
+pub struct Flat;
+
+impl Engine for Flat {
+    fn name(&self) -> &'static str {
+        "flat"
+    }
+
+    fn execute(&self, case: &Case) -> Outcome {
+        let mut memory = case.memory.clone();
+        let mut input = case.input.iter().copied();
+        let mut outputs = Vec::new();
+        let (mut pc, mut base) = (0usize, 0i64);
+        let mut halted = false;
+
+        let address = |memory: &Vec<i64>, pc: usize, base: i64, n: u32| -> usize {
+            let raw = memory.get(pc + n as usize).copied().unwrap_or(0);
+            let mode = memory[pc] / 10i64.pow(n + 1) % 10;
+            match mode {
+                0 => raw as usize,
+                1 => pc + n as usize,
+                2 => (base + raw) as usize,
+                _ => panic!("invalid mode {} at {}", mode, pc),
+            }
+        };
+
+        for _ in 0..STEP_LIMIT {
+            let read = |memory: &Vec<i64>, n| {
+                let a = address(memory, pc, base, n);
+                memory.get(a).copied().unwrap_or(0)
+            };
+            let write = |memory: &mut Vec<i64>, a: usize, value| {
+                if a >= memory.len() {
+                    memory.resize(a + 1, 0);
+                }
+                memory[a] = value;
+            };
+
+            match memory[pc] % 100 {
+                op @ 1 | op @ 2 | op @ 7 | op @ 8 => {
+                    let (x, y) = (read(&memory, 1), read(&memory, 2));
+                    let value = match op {
+                        1 => x + y,
+                        2 => x * y,
+                        7 => (x < y) as i64,
+                        _ => (x == y) as i64,
+                    };
+                    let a = address(&memory, pc, base, 3);
+                    write(&mut memory, a, value);
+                    pc += 4;
+                }
+                3 => match input.next() {
+                    Some(value) => {
+                        let a = address(&memory, pc, base, 1);
+                        write(&mut memory, a, value);
+                        pc += 2;
+                    }
+                    None => break,
+                },
+                4 => {
+                    outputs.push(read(&memory, 1));
+                    pc += 2;
+                }
+                op @ 5 | op @ 6 => {
+                    if (read(&memory, 1) != 0) == (op == 5) {
+                        pc = read(&memory, 2) as usize;
+                    } else {
+                        pc += 3;
+                    }
+                }
+                9 => {
+                    base += read(&memory, 1);
+                    pc += 2;
+                }
+                99 => {
+                    halted = true;
+                    break;
+                }
+                op => panic!("invalid opcode {} at {}", op, pc),
+            }
+        }
+
+        memory.resize(memory.len().max(case.memory.len()), 0);
+        memory.truncate(case.memory.len());
+
+        Outcome {
+            outputs,
+            halted,
+            memory,
+        }
+    }
+}
+
+pub struct Day2;
+
+impl Engine for Day2 {
+    fn name(&self) -> &'static str {
+        "day2"
+    }
+
+    fn supports(&self, case: &Case) -> bool {
+        case.profile == Profile::Day2
+    }
+
+    fn execute(&self, case: &Case) -> Outcome {
+        let mut memory: Vec<u32> = case.memory.iter().map(|v| *v as u32).collect();
+        day2::run(&mut memory);
+
+        Outcome {
+            outputs: Vec::new(),
+            halted: true,
+            memory: memory.into_iter().map(i64::from).collect(),
+        }
+    }
+}
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Mismatch {
+    pub reference: &'static str,
+    pub engine: &'static str,
+    pub expected: Result<Outcome, String>,
+    pub actual: Result<Outcome, String>,
+}
+
+fn execute(engine: &dyn Engine, case: &Case) -> Result<Outcome, String> {
+    panic::catch_unwind(AssertUnwindSafe(|| engine.execute(case))).map_err(|e| {
+        e.downcast_ref::<String>()
+            .cloned()
+            .or_else(|| e.downcast_ref::<&str>().map(|s| s.to_string()))
+            .unwrap_or_else(|| String::from("panicked"))
+    })
+}
+
+// Runs the case on every engine that supports it and compares each result
+// against the first one. Generated programs always halt, so the first engine
+// panicking or running out of steps or input is a failure in its own right,
+// reported as a mismatch with itself.
+pub fn check(engines: &[&dyn Engine], case: &Case) -> Result<(), Box<Mismatch>> {
+    let mut engines = engines.iter().filter(|e| e.supports(case));
+    let reference = match engines.next() {
+        Some(e) => e,
+        None => return Ok(()),
+    };
+    let expected = execute(*reference, case);
+
+    if !expected.as_ref().is_ok_and(|outcome| outcome.halted) {
+        return Err(Box::new(Mismatch {
+            reference: reference.name(),
+            engine: reference.name(),
+            expected: Err(String::from("halts without panicking")),
+            actual: expected,
+        }));
+    }
+
+    for engine in engines {
+        let actual = execute(*engine, case);
+        if actual != expected {
+            return Err(Box::new(Mismatch {
+                reference: reference.name(),
+                engine: engine.name(),
+                expected,
+                actual,
+            }));
+        }
+    }
+
+    Ok(())
+}
+
+pub fn minimize<F: FnMut(&Program) -> bool>(program: &Program, mut fails: F) -> Program {
+    let mut smallest = program.clone();
+
+    'shrink: loop {
+        for candidate in smallest.shrinks() {
+            if candidate.is_valid() && fails(&candidate) {
+                smallest = candidate;
+                continue 'shrink;
+            }
+        }
+
+        return smallest;
+    }
+}
+
+#[derive(Clone, Debug)]
+pub struct Failure {
+    pub program: Program,
+    pub mismatch: Box<Mismatch>,
+}
+
+impl Failure {
+    // Writes the assembled case to `dir`, named after its contents, so that
+    // `regressions` picks it up.
+    pub fn save(&self, dir: &Path) -> io::Result<PathBuf> {
+        let case = self.program.assemble().to_string();
+        let mut hasher = DefaultHasher::new();
+        case.hash(&mut hasher);
+
+        fs::create_dir_all(dir)?;
+        let path = dir.join(format!("{:016x}.txt", hasher.finish()));
+        fs::write(&path, case)?;
+        Ok(path)
+    }
+}
+
+pub fn fuzz(
+    engines: &[&dyn Engine],
+    profile: Profile,
+    seed: u64,
+    iterations: usize,
+) -> Result<(), Failure> {
+    let mut generator = Generator::new(seed, profile);
+
+    for _ in 0..iterations {
+        let program = generator.program();
+
+        if check(engines, &program.assemble()).is_err() {
+            let program = minimize(&program, |p| check(engines, &p.assemble()).is_err());
+            let mismatch = check(engines, &program.assemble()).unwrap_err();
+            return Err(Failure { program, mismatch });
+        }
+    }
+
+    Ok(())
+}
+
+pub fn regressions(dir: &Path) -> io::Result<Vec<(PathBuf, Case)>> {
+    let mut cases = Vec::new();
+
+    if !dir.exists() {
+        return Ok(cases);
+    }
+
+    for entry in fs::read_dir(dir)? {
+        let path = entry?.path();
+        if path.extension().is_some_and(|e| e == "txt") {
+            let case = fs::read_to_string(&path)?
+                .parse()
+                .map_err(|e| io::Error::new(io::ErrorKind::InvalidData, e))?;
+            cases.push((path, case));
+        }
+    }
+
+    cases.sort_by(|a, b| a.0.cmp(&b.0));
+    Ok(cases)
+}
+
+pub fn regressions_dir() -> PathBuf {
+    Path::new(env!("CARGO_MANIFEST_DIR")).join("tests/regressions/intcode")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const ENGINES: &[&dyn Engine] = &[&Reference, &Flat, &Day2];
+
+    fn fuzz_and_save(profile: Profile, seed: u64, iterations: usize) {
+        if let Err(failure) = fuzz(ENGINES, profile, seed, iterations) {
+            let path = failure.save(&regressions_dir()).unwrap();
+            panic!("{:?}\nsaved to {}", failure.mismatch, path.display());
+        }
+    }
+
+    #[test]
+    fn engines_agree() {
+        fuzz_and_save(Profile::Full, 2019, 300);
+        fuzz_and_save(Profile::Day2, 2019, 300);
+    }
+
+    #[test]
+    #[ignore]
+    fn engines_agree_long() {
+        for seed in 1..=20 {
+            fuzz_and_save(Profile::Full, seed, 5_000);
+            fuzz_and_save(Profile::Day2, seed, 5_000);
+        }
+    }
+
+    #[test]
+    fn saved_regressions() {
+        for (path, case) in regressions(&regressions_dir()).unwrap() {
+            if let Err(mismatch) = check(ENGINES, &case) {
+                panic!("{}: {:?}", path.display(), mismatch);
+            }
+        }
+    }
+
+    #[test]
+    fn generated_programs_halt() {
+        let mut generator = Generator::new(7, Profile::Full);
+
+        for _ in 0..100 {
+            let program = generator.program();
+            assert!(program.is_valid());
+            assert!(Reference.execute(&program.assemble()).halted);
+        }
+    }
+
+    // Outputs one too many whenever it sees an output above 3.
+    struct OffByOne;
+
+    impl Engine for OffByOne {
+        fn name(&self) -> &'static str {
+            "off-by-one"
+        }
+
+        fn execute(&self, case: &Case) -> Outcome {
+            let mut outcome = Reference.execute(case);
+            if outcome.outputs.iter().any(|v| *v > 3) {
+                outcome.outputs.push(0);
+            }
+            outcome
+        }
+    }
+
+    #[test]
+    fn minimizes_failures() {
+        let engines: &[&dyn Engine] = &[&Reference, &OffByOne];
+        let failure = fuzz(engines, Profile::Full, 1, 1_000).unwrap_err();
+
+        assert_eq!(failure.mismatch.engine, "off-by-one");
+        assert_eq!(failure.program.body.len(), 1);
+        assert!(check(engines, &failure.program.assemble()).is_err());
+    }
+
+    // Never gets past the first instruction.
+    struct Stuck;
+
+    impl Engine for Stuck {
+        fn name(&self) -> &'static str {
+            "stuck"
+        }
+
+        fn execute(&self, case: &Case) -> Outcome {
+            Outcome {
+                outputs: Vec::new(),
+                halted: false,
+                memory: case.memory.clone(),
+            }
+        }
+    }
+
+    #[test]
+    fn lone_engine_must_halt() {
+        let case = Generator::new(5, Profile::Full).program().assemble();
+        assert_eq!(check(&[&Reference], &case), Ok(()));
+
+        let mismatch = check(&[&Stuck, &Reference], &case).unwrap_err();
+        assert_eq!((mismatch.reference, mismatch.engine), ("stuck", "stuck"));
+
+        let panics: Case = "profile: full\nmemory: 1,0,0,0,42\ninput:".parse().unwrap();
+        let mismatch = check(&[&Reference], &panics).unwrap_err();
+        assert!(mismatch.actual.is_err());
+    }
+
+    #[test]
+    fn flat_matches_reference() {
+        let case: Case = "profile: full\nmemory: 109,19,203,0,204,0,1201,0,5,30,4,30,1105,1,16,0,99,4,31,99\ninput: 7"
+            .parse()
+            .unwrap();
+        let outcome = Flat.execute(&case);
+        assert!(outcome.halted);
+        assert_eq!(outcome.outputs, vec![7, 12]);
+        assert_eq!(outcome, Reference.execute(&case));
+    }
+
+    #[test]
+    fn case_round_trip() {
+        let case = Generator::new(3, Profile::Full).program().assemble();
+        assert_eq!(case.to_string().parse(), Ok(case));
+    }
+}
@@ -0,0 +1,144 @@
+use super::Intcode;
+
+// What one executed instruction overwrote, enough to run it in reverse.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct Undo {
+    pub pc: usize,
+    pub relative_base: i64,
+    // the address written and what it held before, if anything
+    pub write: Option<(usize, Option<i64>)>,
+}
+
+impl Intcode {
+    // Starts keeping an undo log for every instruction executed from here on.
+    // Values taken from an I/O device and stores to memory-mapped devices
+    // are not given back when stepping backwards.
+    pub fn record_history(&mut self) {
+        if self.history.is_none() {
+            self.history = Some(Vec::new());
+        }
+    }
+
+    pub fn history(&self) -> &[Undo] {
+        self.history.as_deref().unwrap_or(&[])
+    }
+
+    // Undoes the most recent instruction. Returns false when there is nothing
+    // left in the log.
+    pub fn step_back(&mut self) -> bool {
+        let undo = match self.history.as_mut().and_then(Vec::pop) {
+            Some(undo) => undo,
+            None => return false,
+        };
+
+        match undo.write {
+            Some((addr, Some(value))) => {
+                self.memory.insert(addr, value);
+            }
+            Some((addr, None)) => {
+                self.memory.remove(&addr);
+            }
+            None => {}
+        }
+
+        self.pc = undo.pc;
+        self.relative_base = undo.relative_base;
+        self.halted = false;
+        self.error = None;
+        self.cycles -= 1;
+        true
+    }
+
+    // Steps backwards until exactly `cycles` instructions have executed.
+    pub fn rewind(&mut self, cycles: usize) -> bool {
+        while self.cycles > cycles {
+            if !self.step_back() {
+                return false;
+            }
+        }
+
+        self.cycles == cycles
+    }
+
+    // The instruction count at which the last logged write to `addr` ran.
+    pub fn last_write(&self, addr: usize) -> Option<usize> {
+        let history = self.history.as_ref()?;
+        let first = self.cycles - history.len();
+
+        history
+            .iter()
+            .rposition(|u| matches!(u.write, Some((a, _)) if a == addr))
+            .map(|i| first + i)
+    }
+
+    // Rewinds to just before the instruction that last wrote `addr`, so the
+    // next step repeats that write.
+    pub fn rewind_to_last_write(&mut self, addr: usize) -> Option<usize> {
+        let cycle = self.last_write(addr)?;
+        self.rewind(cycle);
+        Some(cycle)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::intcode::{parse_program, Intcode, Queues};
+
+    // reads n, then counts [20] down from n to 0 while outputting each value
+    const COUNTDOWN: &str = "3,20,4,20,1001,20,-1,20,1005,20,2,99";
+
+    #[test]
+    fn rewind_to_start() {
+        let program = parse_program(COUNTDOWN).unwrap();
+        let mut computer = Intcode::new(&program);
+        computer.record_history();
+        computer.run(Queues::new(vec![3]));
+
+        assert!(computer.halted);
+        assert_eq!(computer.cycles, 1 + 3 * 3 + 1);
+        assert_eq!(computer.history().len(), computer.cycles);
+
+        assert!(computer.rewind(0));
+        assert!(!computer.halted);
+        assert_eq!(computer.pc, 0);
+        assert_eq!(computer.memory, Intcode::new(&program).memory);
+        assert!(!computer.step_back());
+    }
+
+    #[test]
+    fn last_write() {
+        let program = parse_program(COUNTDOWN).unwrap();
+        let mut computer = Intcode::new(&program);
+        computer.record_history();
+        computer.run(Queues::new(vec![3]));
+
+        assert_eq!(computer.last_write(20), Some(8));
+        assert_eq!(computer.last_write(0), None);
+
+        assert_eq!(computer.rewind_to_last_write(20), Some(8));
+        assert_eq!(computer.pc, 4);
+        assert_eq!(computer.peek(20), 1);
+
+        let mut io = Queues::default();
+        computer.run(&mut io);
+        assert!(computer.halted);
+        assert_eq!(computer.peek(20), 0);
+        assert!(io.output.is_empty());
+    }
+
+    #[test]
+    fn history_starts_midway() {
+        let program = parse_program(COUNTDOWN).unwrap();
+        let mut computer = Intcode::new(&program);
+        let mut io = Queues::new(vec![2]);
+        computer.step(&mut io);
+        computer.step(&mut io);
+        computer.record_history();
+        computer.run(&mut io);
+
+        assert_eq!(computer.last_write(20), Some(5));
+        assert!(!computer.rewind(1));
+        assert_eq!(computer.cycles, 2);
+        assert_eq!(computer.peek(20), 2);
+    }
+}
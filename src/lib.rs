@@ -1,5 +1,3 @@
-#![feature(is_sorted)]
-
 #[macro_use]
 extern crate aoc_runner_derive;
 extern crate aoc_runner;
@@ -13,5 +11,8 @@ pub mod day6;
 pub mod day7;
 pub mod day8;
 pub mod day9;
+mod input;
+pub mod intcode;
+mod xorshift;
 
 aoc_lib! { year = 2019 }
@@ -0,0 +1,45 @@
+// A small xorshift64 generator, shared by the code that needs repeatable
+// pseudo-random numbers. Not suitable for anything but tests and puzzles.
+#[derive(Clone, Debug)]
+pub struct XorShift {
+    state: u64,
+}
+
+impl XorShift {
+    // A zero state would only ever yield zeros, so it is bumped to one.
+    pub fn new(seed: u64) -> XorShift {
+        XorShift { state: seed.max(1) }
+    }
+
+    pub fn next_u64(&mut self) -> u64 {
+        self.state ^= self.state << 13;
+        self.state ^= self.state >> 7;
+        self.state ^= self.state << 17;
+        self.state
+    }
+
+    // A number in 0..n.
+    pub fn below(&mut self, n: usize) -> usize {
+        (self.next_u64() % n as u64) as usize
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn repeatable() {
+        let take = |seed| {
+            let mut rng = XorShift::new(seed);
+            (0..5).map(|_| rng.next_u64()).collect::<Vec<_>>()
+        };
+        assert_eq!(take(7), take(7));
+        assert_ne!(take(7), take(8));
+        assert_eq!(take(0), take(1));
+        assert!(!take(0).contains(&0));
+
+        let mut rng = XorShift::new(3);
+        assert!((0..100).all(|_| rng.below(6) < 6));
+    }
+}